@@ -1,6 +1,9 @@
 use std::cmp;
+use std::collections::BTreeMap;
+use std::fmt;
 use std::str::FromStr;
 
+use prettytable::cell::Cell;
 use prettytable::row::Row;
 use regex::Regex;
 
@@ -27,6 +30,17 @@ impl Benchmarks {
     pub fn paired(self) -> PairedBenchmarks {
         PairedBenchmarks::from(self)
     }
+
+    /// Create a set of pairwise comparisons between benchmarks, where the old
+    /// and new benchmarks are paired based on whether `key` gives them
+    /// equivalent names.
+    ///
+    /// The names of the benchmarks themselves are left untouched.
+    pub fn paired_by<F>(self, key: F) -> PairedBenchmarks
+        where F: Fn(&str) -> String
+    {
+        PairedBenchmarks::by_key(self, key)
+    }
 }
 
 /// `PairedBenchmarks` is a set of paired benchmarks.
@@ -35,25 +49,98 @@ impl Benchmarks {
 #[derive(Clone, Debug)]
 pub struct PairedBenchmarks {
     cmps: Vec<Comparison>,
+    /// The position of the old benchmark of each comparison in its input.
+    positions: Vec<usize>,
     unpaired_old: Vec<Benchmark>,
     unpaired_new: Vec<Benchmark>,
 }
 
 impl From<Benchmarks> for PairedBenchmarks {
-    fn from(mut benches: Benchmarks) -> PairedBenchmarks {
-        benches.old.sort();
-        benches.new.sort();
-        let ov = Overlap::find(benches.old, benches.new, Benchmark::cmp);
-        let cmps = ov.overlap.into_iter().map(|(a, b)| a.compare(b)).collect();
+    fn from(benches: Benchmarks) -> PairedBenchmarks {
+        PairedBenchmarks::by_key(benches, str::to_string)
+    }
+}
+
+impl PairedBenchmarks {
+    /// Pairs up the old and new benchmarks on the names given by `key`.
+    fn by_key<F>(benches: Benchmarks, key: F) -> PairedBenchmarks
+        where F: Fn(&str) -> String
+    {
+        let keyed = |benches: Vec<Benchmark>| {
+            let mut keyed: Vec<(String, usize, Benchmark)> =
+                benches.into_iter().enumerate().map(|(i, b)| (key(&b.name), i, b)).collect();
+            keyed.sort_by(|a, b| a.0.cmp(&b.0));
+            keyed
+        };
+        let ov = Overlap::find(keyed(benches.old), keyed(benches.new), |a, b| a.0.cmp(&b.0));
+        let (positions, mut cmps): (Vec<usize>, Vec<Comparison>) = ov.overlap
+            .into_iter()
+            .map(|((_, i, a), (_, _, b))| (i, a.compare(b)))
+            .unzip();
+        let old_ranks = ranks(&cmps.iter().map(|c| c.old.ns).collect::<Vec<u64>>());
+        let new_ranks = ranks(&cmps.iter().map(|c| c.new.ns).collect::<Vec<u64>>());
+        for (i, cmp) in cmps.iter_mut().enumerate() {
+            cmp.rank_change = new_ranks[i] as i64 - old_ranks[i] as i64;
+        }
         PairedBenchmarks {
             cmps: cmps,
-            unpaired_old: ov.left,
-            unpaired_new: ov.right,
+            positions: positions,
+            unpaired_old: ov.left.into_iter().map(|(_, _, b)| b).collect(),
+            unpaired_new: ov.right.into_iter().map(|(_, _, b)| b).collect(),
         }
     }
-}
 
-impl PairedBenchmarks {
+    /// Puts the comparisons in the order their old benchmarks appear in the
+    /// input, instead of ordering them by name.
+    pub fn in_input_order(self) -> PairedBenchmarks {
+        let mut ordered: Vec<(usize, Comparison)> =
+            self.positions.into_iter().zip(self.cmps).collect();
+        ordered.sort_by_key(|&(i, _)| i);
+        let (positions, cmps) = ordered.into_iter().unzip();
+        PairedBenchmarks {
+            cmps: cmps,
+            positions: positions,
+            unpaired_old: self.unpaired_old,
+            unpaired_new: self.unpaired_new,
+        }
+    }
+
+    /// Compares the benchmarks that report a throughput in both old and new
+    /// on their throughput instead of their ns/iter, keeping the rank change
+    /// by ns/iter.
+    pub fn auto_metric(mut self) -> PairedBenchmarks {
+        for cmp in &mut self.cmps {
+            let old = cmp.old.clone().into_throughput();
+            let new = cmp.new.clone().into_throughput();
+            if let (Some(old), Some(new)) = (old, new) {
+                *cmp = Comparison {
+                    rank_change: cmp.rank_change,
+                    by_throughput: true,
+                    ..old.compare(new)
+                };
+            }
+        }
+        self
+    }
+
+    /// Makes the percentage change of every comparison relative to the
+    /// given base. See `Comparison::ratio_to`.
+    pub fn with_ratio_base(mut self, base: RatioBase) -> PairedBenchmarks {
+        for cmp in &mut self.cmps {
+            cmp.diff_ratio = cmp.ratio_to(base);
+        }
+        self
+    }
+
+    /// Makes the percentage change of every comparison relative to the
+    /// smaller of its two measurements. See `Comparison::symmetric_ratio`.
+    pub fn symmetric(mut self) -> PairedBenchmarks {
+        for cmp in &mut self.cmps {
+            cmp.diff_ratio = cmp.symmetric_ratio();
+        }
+        self
+    }
+
     /// Returns all pairwise benchmark comparisons.
     ///
     /// Each comparison provides access to the old and new benchmarks.
@@ -75,12 +162,15 @@ impl PairedBenchmarks {
 }
 
 /// All extractable data from a single micro-benchmark.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, RustcDecodable, RustcEncodable)]
 pub struct Benchmark {
     pub name: String,
     pub ns: u64,
     pub variance: u64,
     pub throughput: Option<u64>,
+    pub iterations: Option<usize>,
+    /// The ns/iter at some percentiles, e.g. `p95`, if reported.
+    pub percentiles: Option<BTreeMap<String, u64>>,
 }
 
 impl Eq for Benchmark {}
@@ -105,13 +195,32 @@ impl PartialOrd for Benchmark {
 
 lazy_static! {
     static ref BENCHMARK_REGEX: Regex = Regex::new(r##"(?x)
-        test\s+(?P<name>\S+)                        # test   mod::test_name
-        \s+...\sbench:\s+(?P<ns>[0-9,]+)\s+ns/iter  # ... bench: 1234 ns/iter
-        \s+\(\+/-\s+(?P<variance>[0-9,]+)\)         # (+/- 4321)
-        (?:\s+=\s+(?P<throughput>[0-9,]+)\sMB/s)?   # =   2314 MB/s
+        test\s+(?P<name>\S(?:.*?\S)??)              # test   mod::test_name<A, B>
+        (?:\s+(?:-\s+should\s+panic|should_panic     #   - should panic
+            |\(?ignored\)?))*                       #   (ignored)
+        \s+\.\.\.                                   # ...
+        \sbench:\s+(?P<ns>[0-9,_]+)\s+ns/iter       # bench: 1234 ns/iter
+        (?:\s+\(\+/-\s*(?P<variance>[^)]*)\))?      # (+/- 4321)
+        (?:\s+=\s+(?P<throughput>[0-9,_]+)\sMB/s)?  # =   2314 MB/s
+        (?:\s+\((?P<iterations>[0-9,_]+)\s           # (1000 iters)
+            iter(?:ation)?s\))?
+    "##).unwrap();
+
+    static ref THROUGHPUT_REGEX: Regex = Regex::new(r##"(?x)
+        test\s+(?P<name>\S(?:.*?\S)??)              # test   mod::test_name
+        (?:\s+(?:-\s+should\s+panic|should_panic     #   - should panic
+            |\(?ignored\)?))*                       #   (ignored)
+        \s+\.\.\.                                   # ...
+        \sbench:\s+(?P<throughput>[0-9,_]+)\s+MB/s   # bench: 2314 MB/s
+        (?:\s+\(\+/-\s*(?P<variance>[^)]*)\))?      # (+/- 12)
     "##).unwrap();
 }
 
+/// The number of standard deviations that the range of the samples of a
+/// benchmark is assumed to span, following the range rule of thumb for
+/// normally distributed measurements.
+pub const RANGE_IN_STDDEVS: f64 = 4f64;
+
 impl FromStr for Benchmark {
     type Err = ();
 
@@ -121,49 +230,260 @@ impl FromStr for Benchmark {
             None => return Err(()),
             Some(caps) => caps,
         };
-        let ns = match parse_commas(&caps["ns"]) {
+        let ns = match parse_separated(&caps["ns"]) {
             None => return Err(()),
             Some(ns) => ns,
         };
-        let variance = match parse_commas(&caps["variance"]) {
-            None => return Err(()),
-            Some(variance) => variance,
-        };
-        let throughput = caps.name("throughput").and_then(parse_commas);
+        // The variance is supplementary, so a missing or malformed one is
+        // taken to be zero. See `lacks_variance`.
+        let variance = caps.name("variance").and_then(parse_separated).unwrap_or(0);
+        let throughput = caps.name("throughput").and_then(parse_separated);
+        let iterations = caps.name("iterations").and_then(parse_separated).map(|n| n as usize);
         Ok(Benchmark {
             name: caps["name"].to_string(),
             ns: ns,
             variance: variance,
             throughput: throughput,
+            iterations: iterations,
+            percentiles: None,
         })
     }
 }
 
+impl fmt::Display for Benchmark {
+    /// Formats the benchmark as a line of libtest output, which parses back
+    /// into the same benchmark, apart from its percentiles.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f,
+                    "test {} ... bench: {:>11} ns/iter (+/- {})",
+                    self.name,
+                    commafy(self.ns, ","),
+                    commafy(self.variance, ",")));
+        if let Some(throughput) = self.throughput {
+            try!(write!(f, " = {} MB/s", throughput));
+        }
+        if let Some(iterations) = self.iterations {
+            try!(write!(f, " ({} iters)", iterations));
+        }
+        Ok(())
+    }
+}
+
+/// Returns whether a benchmark line has no variance, or one that is not a
+/// number, e.g. `(+/- NaN)`. Such a benchmark is parsed with a variance of 0.
+pub fn lacks_variance(line: &str) -> bool {
+    BENCHMARK_REGEX.captures(line)
+        .map_or(false, |caps| caps.name("variance").and_then(parse_separated).is_none())
+}
+
 impl Benchmark {
+    /// Parses a benchmark line for comparing throughput instead of ns/iter:
+    /// the throughput in MB/s takes the place of the ns/iter.
+    ///
+    /// Besides the usual lines, this parses lines that only report a
+    /// throughput, like `test name ... bench: 2,314 MB/s (+/- 12)`, where
+    /// the variance is in MB/s. The variance of a usual line is in ns/iter,
+    /// so it is taken to be 0. Lines without a throughput give `None`.
+    pub fn parse_throughput(line: &str) -> Option<Benchmark> {
+        if let Some(caps) = THROUGHPUT_REGEX.captures(line) {
+            return parse_separated(&caps["throughput"]).map(|throughput| {
+                Benchmark {
+                    name: caps["name"].to_string(),
+                    ns: throughput,
+                    variance: caps.name("variance").and_then(parse_separated).unwrap_or(0),
+                    throughput: None,
+                    iterations: None,
+                    percentiles: None,
+                }
+            });
+        }
+        line.parse::<Benchmark>().ok().and_then(Benchmark::into_throughput)
+    }
+
+    /// Formats this benchmark as a record of the InfluxDB line protocol in
+    /// the `bench` measurement, tagged with its name and `tags`, with the
+    /// ns/iter and variance as fields and `timestamp` in ns since the epoch.
+    pub fn to_influx(&self, tags: &[(String, String)], timestamp: u64) -> String {
+        let mut record = format!("bench,name={}", influx_escape(&self.name));
+        for &(ref key, ref value) in tags {
+            record.push_str(&format!(",{}={}", influx_escape(key), influx_escape(value)));
+        }
+        format!("{} ns={},variance={} {}", record, self.ns, self.variance, timestamp)
+    }
+
+    /// Puts the ns/iter at the given percentile, e.g. `p95`, in the place of
+    /// the ns/iter of this benchmark. Gives `None` if the percentile isn't
+    /// reported.
+    pub fn at_percentile(self, percentile: &str) -> Option<Benchmark> {
+        let ns = self.percentiles.as_ref().and_then(|p| p.get(percentile).cloned());
+        ns.map(|ns| Benchmark { ns: ns, ..self })
+    }
+
+    /// Puts the throughput of this benchmark in the place of its ns/iter,
+    /// dropping the variance as it is in ns/iter. Gives `None` if there is no
+    /// throughput.
+    pub fn into_throughput(self) -> Option<Benchmark> {
+        self.throughput.map(|throughput| {
+            Benchmark {
+                ns: throughput,
+                variance: 0,
+                throughput: None,
+                ..self
+            }
+        })
+    }
+
     /// Compares an old benchmark (self) with a new benchmark.
     pub fn compare(self, new: Benchmark) -> Comparison {
         let diff_ns = new.ns as i64 - self.ns as i64;
-        let diff_ratio = diff_ns as f64 / self.ns as f64;
+        let diff_ratio = ratio(diff_ns, self.ns as f64);
         Comparison {
             old: self,
             new: new,
             diff_ns: diff_ns,
             diff_ratio: diff_ratio,
+            rank_change: 0,
+            by_throughput: false,
+        }
+    }
+
+    /// Returns the estimated standard deviation of this benchmark.
+    ///
+    /// libtest reports the range of its (winsorized) samples as `(+/- X)`,
+    /// not their standard deviation, so by default the variance is divided
+    /// by `RANGE_IN_STDDEVS`. If `variance_is_stddev`, the variance is taken
+    /// to be the standard deviation as is, e.g. for other benchmark tools.
+    pub fn stddev(&self, variance_is_stddev: bool) -> f64 {
+        if variance_is_stddev {
+            self.variance as f64
+        } else {
+            self.variance as f64 / RANGE_IN_STDDEVS
         }
     }
 
-    fn fmt_ns(&self, variance: bool) -> String {
-        let mut res = commafy(self.ns);
-        if variance {
+    /// Returns the lower and upper bound of this benchmark, i.e., its ns/iter
+    /// minus and plus its variance.
+    fn bounds(&self) -> (u64, u64) {
+        (self.ns.saturating_sub(self.variance), self.ns.saturating_add(self.variance))
+    }
+
+    fn fmt_ns(&self, fmt: &RowFormat) -> String {
+        let mut res = commafy(round_to(self.ns, fmt.round), &fmt.separator);
+        if fmt.variance && !fmt.columns.contains(&Column::Variance) {
             res = format!("{} (+/- {})", res, self.variance);
         }
-        if let Some(throughput) = self.throughput {
-            res = format!("{} ({} MB/s)", res, throughput);
+        if let (false, Some(throughput)) = (fmt.columns.contains(&Column::Throughput),
+                                            self.throughput) {
+            res = format!("{} ({})", res, fmt_throughput(throughput));
+        }
+        if let (true, Some(iterations)) = (fmt.iterations, self.iterations) {
+            res = format!("{} ({} iters)", res, commafy(iterations as u64, &fmt.separator));
         }
         res
     }
 }
 
+/// A column of the comparison table.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Column {
+    /// The name of the benchmark.
+    Name,
+    /// The old measurement.
+    Fst,
+    /// The new measurement.
+    Snd,
+    /// The difference in ns/iter.
+    DiffNs,
+    /// The difference in percent.
+    DiffPct,
+    /// The difference in ns/iter followed by the difference in percent.
+    Diff,
+    /// The old and new variance.
+    Variance,
+    /// The old and new throughput.
+    Throughput,
+    /// How many times faster the new measurement is than the old one.
+    Speedup,
+    /// The new variance relative to the old variance.
+    VarianceDiff,
+    /// How many places the benchmark moved when ranked by ns/iter.
+    Rank,
+    /// A bar for the old and new ns/iter, scaled to the slowest of the two.
+    Spark,
+}
+
+impl Column {
+    /// The columns of the table when no columns are selected.
+    pub fn defaults() -> Vec<Column> {
+        vec![Column::Name, Column::Fst, Column::Snd, Column::DiffNs, Column::DiffPct]
+    }
+
+    /// The names of all columns, in the form they are parsed from.
+    pub fn names() -> &'static [&'static str] {
+        &["name",
+          "fst",
+          "snd",
+          "diff_ns",
+          "diff_pct",
+          "diff",
+          "variance",
+          "throughput",
+          "speedup",
+          "variance_diff",
+          "rank",
+          "spark"]
+    }
+}
+
+impl FromStr for Column {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Column, ()> {
+        match name {
+            "name" => Ok(Column::Name),
+            "fst" => Ok(Column::Fst),
+            "snd" => Ok(Column::Snd),
+            "diff_ns" => Ok(Column::DiffNs),
+            "diff_pct" => Ok(Column::DiffPct),
+            "diff" => Ok(Column::Diff),
+            "variance" => Ok(Column::Variance),
+            "throughput" => Ok(Column::Throughput),
+            "speedup" => Ok(Column::Speedup),
+            "variance_diff" => Ok(Column::VarianceDiff),
+            "rank" => Ok(Column::Rank),
+            "spark" => Ok(Column::Spark),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Options for formatting a comparison as a table row.
+#[derive(Clone, Debug)]
+pub struct RowFormat {
+    /// The columns of the row, in order. Selecting the variance or
+    /// throughput column moves it out of the measurement cells.
+    pub columns: Vec<Column>,
+    /// Show the variance of each measurement.
+    pub variance: bool,
+    /// Follow each measurement by its lower and upper bound.
+    pub bounds: bool,
+    /// Show the iteration count of each measurement, if known.
+    pub iterations: bool,
+    /// The number of decimal places of the percent difference.
+    pub precision: usize,
+    /// The number of significant digits to round the ns/iter and the
+    /// difference in ns/iter to, if any.
+    pub round: Option<usize>,
+    /// The thousands separator.
+    pub separator: String,
+    /// Show each measurement as a ratio to the old one instead of in ns/iter.
+    pub relative: bool,
+    /// Prefix the percent difference with an arrow pointing up or down.
+    pub arrows: bool,
+    /// Draw the spark column with ASCII characters instead of blocks.
+    pub ascii: bool,
+}
+
 /// A comparison between an old and a new benchmark.
 /// All differences are reported in terms of measuring improvements
 /// (negative) or regressions (positive). That is, if an old benchmark
@@ -175,34 +495,607 @@ pub struct Comparison {
     pub old: Benchmark,
     pub new: Benchmark,
     pub diff_ns: i64,
+    /// The difference relative to the old ns/iter, or 0 if that is 0, so
+    /// that it is always finite. See `ratio`.
     pub diff_ratio: f64,
+    /// How many places the benchmark moved down when ranking the compared
+    /// benchmarks from fastest to slowest, from old to new.
+    pub rank_change: i64,
+    /// Whether the throughput of the benchmarks is compared instead of their
+    /// ns/iter, so that bigger is better.
+    pub by_throughput: bool,
+}
+
+/// Whether a comparison is an improvement, a regression or neither.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Improvement,
+    Regression,
+    Neutral,
+}
+
+impl Direction {
+    /// The name of the direction in structured output.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Direction::Improvement => "improvement",
+            Direction::Regression => "regression",
+            Direction::Neutral => "neutral",
+        }
+    }
+}
+
+/// A comparison as it is serialized in structured (JSON and CSV) output.
+///
+/// `change_pct` is the signed change in ns/iter from old to new, in percent,
+/// so it is positive when the new benchmark takes longer. `direction` says
+/// whether the change is an improvement or a regression, so consumers don't
+/// have to derive it from the sign, which depends on --bigger-is-better.
+#[derive(Clone, Debug, RustcDecodable, RustcEncodable)]
+pub struct Record {
+    pub name: String,
+    pub old_ns: u64,
+    pub new_ns: u64,
+    pub old_variance: u64,
+    pub new_variance: u64,
+    pub diff_ns: i64,
+    pub change_pct: f64,
+    pub direction: String,
+}
+
+impl Record {
+    /// The header line of CSV output.
+    pub fn csv_header() -> &'static str {
+        "name,old_ns,new_ns,old_variance,new_variance,diff_ns,change_pct,direction"
+    }
+
+    /// Formats the record as a line of CSV output.
+    pub fn to_csv(&self) -> String {
+        format!("{},{},{},{},{},{},{},{}",
+                csv_quote(&self.name),
+                self.old_ns,
+                self.new_ns,
+                self.old_variance,
+                self.new_variance,
+                self.diff_ns,
+                self.change_pct,
+                self.direction)
+    }
+}
+
+/// How a comparison changed between two JSON outputs of benchcmp, as it is
+/// serialized by the trend command.
+///
+/// `old` and `new` are the comparison in either output, or `None` if it is
+/// only in the other one. `change_pct_diff` is the new `change_pct` minus the
+/// old one, in percentage points, if it is in both. All of them are rounded
+/// to hundredths of a percent, see `round_pct`.
+#[derive(Clone, Debug, RustcEncodable)]
+pub struct Trend {
+    pub name: String,
+    pub old: Option<Record>,
+    pub new: Option<Record>,
+    pub change_pct_diff: Option<f64>,
+}
+
+/// Rounds a percentage to hundredths, so the trend command's JSON output
+/// doesn't depend on the last digits of floating point arithmetic.
+fn round_pct(pct: f64) -> f64 {
+    (pct * 100f64).round() / 100f64
+}
+
+/// Pairs up the records of two JSON outputs of benchcmp by name, sorted by
+/// name.
+pub fn trends(old: Vec<Record>, new: Vec<Record>) -> Vec<Trend> {
+    let mut pairs: BTreeMap<String, (Option<Record>, Option<Record>)> = BTreeMap::new();
+    for record in old {
+        let name = record.name.clone();
+        pairs.entry(name).or_insert((None, None)).0 = Some(record);
+    }
+    for record in new {
+        let name = record.name.clone();
+        pairs.entry(name).or_insert((None, None)).1 = Some(record);
+    }
+    pairs.into_iter()
+        .map(|(name, (old, new))| {
+            let change_pct_diff = match (&old, &new) {
+                (&Some(ref old), &Some(ref new)) => {
+                    Some(round_pct(new.change_pct - old.change_pct))
+                }
+                _ => None,
+            };
+            let round = |mut record: Record| {
+                record.change_pct = round_pct(record.change_pct);
+                record
+            };
+            Trend {
+                name: name,
+                old: old.map(&round),
+                new: new.map(&round),
+                change_pct_diff: change_pct_diff,
+            }
+        })
+        .collect()
+}
+
+/// Quotes a CSV field if it contains a separator, quote or line break.
+fn csv_quote(field: &str) -> String {
+    if field.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Escapes the commas, equals signs and spaces in a tag of the InfluxDB line
+/// protocol.
+fn influx_escape(tag: &str) -> String {
+    let mut escaped = String::with_capacity(tag.len());
+    for c in tag.chars() {
+        if c == ',' || c == '=' || c == ' ' {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
 }
 
 impl Comparison {
+    /// Returns whether this comparison is an improvement or a regression.
+    /// A decrease in ns/iter is an improvement, unless `bigger_is_better`.
+    /// Bigger is always better when comparing throughput.
+    pub fn direction(&self, bigger_is_better: bool) -> Direction {
+        match (self.diff_ns.signum(), bigger_is_better || self.by_throughput) {
+            (0, _) => Direction::Neutral,
+            (1, false) | (-1, true) => Direction::Regression,
+            _ => Direction::Improvement,
+        }
+    }
+
+    /// Returns the difference relative to the given base.
+    ///
+    /// Going from 100 to 125 ns/iter is a change of +25% relative to the old
+    /// measurement, +20% relative to the new one and about +22.36% relative
+    /// to their geometric mean.
+    pub fn ratio_to(&self, base: RatioBase) -> f64 {
+        let base = match base {
+            RatioBase::Old => self.old.ns as f64,
+            RatioBase::New => self.new.ns as f64,
+            RatioBase::Geomean => (self.old.ns as f64 * self.new.ns as f64).sqrt(),
+        };
+        ratio(self.diff_ns, base)
+    }
+
+    /// Returns the difference relative to the smaller of the two
+    /// measurements, instead of relative to the old one.
+    ///
+    /// That is, `(new - old) / min(old, new)`. Going from 100 to 200 ns/iter
+    /// and from 200 to 100 ns/iter are then changes of +100% and -100%,
+    /// where `diff_ratio` gives +100% and -50%. Swapping the inputs only
+    /// flips the sign.
+    pub fn symmetric_ratio(&self) -> f64 {
+        ratio(self.diff_ns, cmp::min(self.old.ns, self.new.ns) as f64)
+    }
+
+    /// Returns the relative change in throughput from old to new, or `None`
+    /// when either benchmark doesn't report a throughput, or the old one is
+    /// zero.
+    pub fn throughput_change(&self) -> Option<f64> {
+        match (self.old.throughput, self.new.throughput) {
+            (Some(old), Some(new)) if old > 0 => Some(new as f64 / old as f64 - 1f64),
+            _ => None,
+        }
+    }
+
+    /// Returns the new variance as a factor of the old variance, or `None`
+    /// when only the old variance is zero.
+    pub fn variance_ratio(&self) -> Option<f64> {
+        match (self.old.variance, self.new.variance) {
+            (0, 0) => Some(1f64),
+            (0, _) => None,
+            (old, new) => Some(new as f64 / old as f64),
+        }
+    }
+
+    /// Converts this comparison to its serialized form.
+    pub fn to_record(&self, bigger_is_better: bool) -> Record {
+        Record {
+            name: self.old.name.clone(),
+            old_ns: self.old.ns,
+            new_ns: self.new.ns,
+            old_variance: self.old.variance,
+            new_variance: self.new.variance,
+            diff_ns: self.diff_ns,
+            change_pct: self.diff_ratio * 100f64,
+            direction: self.direction(bigger_is_better).name().to_string(),
+        }
+    }
+
+    /// Returns whether the absolute difference is more than `k` times the
+    /// average standard deviation of the old and new benchmark.
+    /// See `Benchmark::stddev` for the meaning of `variance_is_stddev`.
+    pub fn exceeds_stddev(&self, k: f64, variance_is_stddev: bool) -> bool {
+        let stddev = (self.old.stddev(variance_is_stddev) + self.new.stddev(variance_is_stddev)) /
+                     2f64;
+        self.diff_ns.abs() as f64 > k * stddev
+    }
+
+    /// Formats this comparison like a hunk of a unified diff: a header with
+    /// the name, the old measurement as a removed line, the new measurement
+    /// as an added line and the difference as context.
+    pub fn to_unified(&self, fmt: &RowFormat) -> String {
+        let sign = if self.diff_ns < 0 { "-" } else { "+" };
+        format!("@@ {} @@\n- {} ns/iter\n+ {} ns/iter\n  {}{} ns/iter ({:+.*}%)\n",
+                self.old.name,
+                commafy(self.old.ns, &fmt.separator),
+                commafy(self.new.ns, &fmt.separator),
+                sign,
+                commafy(self.diff_ns.abs() as u64, &fmt.separator),
+                fmt.precision,
+                self.diff_ratio * 100f64)
+    }
+
     /// Convert this comparison to a formatted row useful for printing.
     ///
-    /// The columns of the row are as follows: the name of the benchmark being
-    /// compared, the old measurement, the new measurement, the measurement
-    /// difference and the percent measurement difference. Negative differences
-    /// imply an improvement in performance from old to new.
-    pub fn to_row(&self, variance: bool, regression: bool) -> Row {
+    /// The default columns of the row are as follows: the name of the
+    /// benchmark being compared, the old measurement, the new measurement,
+    /// the measurement difference and the percent measurement difference.
+    /// Negative differences imply an improvement in performance from old to
+    /// new. See `RowFormat` for the optional parts of the row.
+    ///
+    /// Every cell is styled with `style`, a prettytable style specification.
+    pub fn to_row(&self, fmt: &RowFormat, style: &str) -> Row {
         let name = &self.old.name;
-        let fst_ns = self.old.fmt_ns(variance);
-        let snd_ns = self.new.fmt_ns(variance);
-        let diff_ratio = format!("{:.2}%", self.diff_ratio * 100f64);
+        let (fst_ns, snd_ns) = if fmt.relative {
+            (fmt_factor(self.old.ns, self.old.ns, fmt.precision),
+             fmt_factor(self.new.ns, self.old.ns, fmt.precision))
+        } else {
+            (self.old.fmt_ns(fmt), self.new.fmt_ns(fmt))
+        };
+        let (fst_ns, snd_ns) = if self.by_throughput && !fmt.relative {
+            (format!("{} MB/s", fst_ns), format!("{} MB/s", snd_ns))
+        } else {
+            (fst_ns, snd_ns)
+        };
+        let diff_ratio = format!("{:.*}%", fmt.precision, self.diff_ratio * 100f64);
+        let diff_ratio = match (fmt.arrows, self.diff_ns.signum()) {
+            (true, 1) => format!("▲ {}", diff_ratio),
+            (true, -1) => format!("▼ {}", diff_ratio),
+            _ => diff_ratio,
+        };
         let diff_ns = {
-            let diff_ns = commafy(self.diff_ns.abs() as u64);
+            let diff_ns = commafy(round_to(self.diff_ns.abs() as u64, fmt.round),
+                                  &fmt.separator);
             if self.diff_ns < 0 {
                 format!("-{}", diff_ns)
             } else {
                 diff_ns
             }
         };
-        if regression {
-            row![Fr->name, Fr->fst_ns, Fr->snd_ns, rFr->diff_ns, rFr->diff_ratio]
-        } else {
-            row![Fg->name, Fg->fst_ns, Fg->snd_ns, rFg->diff_ns, rFg->diff_ratio]
+        let right = format!("r{}", style);
+        let winner = fastest(&[self.old.ns, self.new.ns], self.by_throughput);
+        let mut cells = vec![];
+        for column in &fmt.columns {
+            match *column {
+                Column::Name => cells.push(Cell::new(name).style_spec(style)),
+                Column::Fst | Column::Snd => {
+                    let (i, bench, ns) = if *column == Column::Fst {
+                        (0, &self.old, &fst_ns)
+                    } else {
+                        (1, &self.new, &snd_ns)
+                    };
+                    if winner == Some(i) && !style.contains('b') {
+                        cells.push(Cell::new(ns).style_spec(&format!("b{}", style)));
+                    } else {
+                        cells.push(Cell::new(ns).style_spec(style));
+                    }
+                    if fmt.bounds {
+                        let (lower, upper) = bench.bounds();
+                        cells.push(Cell::new(&commafy(lower, &fmt.separator))
+                            .style_spec(style));
+                        cells.push(Cell::new(&commafy(upper, &fmt.separator))
+                            .style_spec(style));
+                    }
+                }
+                Column::DiffNs => cells.push(Cell::new(&diff_ns).style_spec(&right)),
+                Column::DiffPct => cells.push(Cell::new(&diff_ratio).style_spec(&right)),
+                Column::Diff => {
+                    let diff = format!("{} ({})", diff_ns, diff_ratio);
+                    cells.push(Cell::new(&diff).style_spec(&right));
+                }
+                Column::Variance => {
+                    let variance = format!("{} / {}",
+                                           commafy(self.old.variance, &fmt.separator),
+                                           commafy(self.new.variance, &fmt.separator));
+                    cells.push(Cell::new(&variance).style_spec(&right));
+                }
+                Column::Throughput => {
+                    let throughput = match (self.old.throughput, self.new.throughput) {
+                        (Some(old), Some(new)) => {
+                            format!("{} / {}", fmt_throughput(old), fmt_throughput(new))
+                        }
+                        _ => String::new(),
+                    };
+                    cells.push(Cell::new(&throughput).style_spec(&right));
+                }
+                Column::Speedup => {
+                    let speedup = fmt_factor(self.old.ns, self.new.ns, fmt.precision);
+                    cells.push(Cell::new(&speedup).style_spec(&right));
+                }
+                Column::VarianceDiff => {
+                    let ratio = match self.variance_ratio() {
+                        Some(ratio) => format!("{:.*}x", fmt.precision, ratio),
+                        None => "n/a".to_string(),
+                    };
+                    cells.push(Cell::new(&ratio).style_spec(&right));
+                }
+                Column::Rank => {
+                    let rank = match self.rank_change {
+                        0 => "0".to_string(),
+                        change => format!("{:+}", change),
+                    };
+                    cells.push(Cell::new(&rank).style_spec(&right));
+                }
+                Column::Spark => {
+                    let spark = sparkline(&[self.old.ns, self.new.ns], fmt.ascii);
+                    cells.push(Cell::new(&spark).style_spec(style));
+                }
+            }
         }
+        Row::new(cells)
+    }
+}
+
+/// What the difference of a comparison is relative to in its percentage
+/// change.
+#[derive(Clone, Copy, Debug, PartialEq, RustcDecodable)]
+pub enum RatioBase {
+    /// The old measurement, which is the default.
+    Old,
+    /// The new measurement.
+    New,
+    /// The geometric mean of the old and new measurement.
+    Geomean,
+}
+
+/// How the runs of a benchmark with the same name are combined.
+#[derive(Clone, Copy, Debug, PartialEq, RustcDecodable)]
+pub enum Reduce {
+    /// Keep the fastest run.
+    Min,
+    /// Keep the slowest run.
+    Max,
+    /// Take the median of every measurement.
+    Median,
+    /// Take the mean of every measurement.
+    Mean,
+}
+
+/// Combines all benchmarks with the same name into a single benchmark.
+///
+/// The combined benchmarks are in the order in which their names first
+/// appear.
+///
+/// `Min` and `Max` keep the whole run with the lowest or highest ns/iter.
+/// `Median` and `Mean` combine the measurements one by one, where the
+/// throughput and iteration count are combined over the benchmarks that
+/// report them.
+pub fn reduce(benches: Vec<Benchmark>, reducer: Reduce) -> Vec<Benchmark> {
+    let mut names = vec![];
+    let mut runs: BTreeMap<String, Vec<Benchmark>> = BTreeMap::new();
+    for bench in benches {
+        if !runs.contains_key(&bench.name) {
+            names.push(bench.name.clone());
+        }
+        runs.entry(bench.name.clone()).or_insert_with(Vec::new).push(bench);
+    }
+    let combine: fn(Vec<u64>) -> Option<u64> = match reducer {
+        Reduce::Min => return pick_runs(names, runs, |runs| runs.iter().min_by_key(|b| b.ns)),
+        Reduce::Max => return pick_runs(names, runs, |runs| runs.iter().max_by_key(|b| b.ns)),
+        Reduce::Median => median,
+        Reduce::Mean => |numbers| mean(numbers.into_iter()),
+    };
+    names.into_iter()
+        .map(|name| {
+            let runs = &runs[&name];
+            Benchmark {
+                name: name,
+                ns: combine(runs.iter().map(|b| b.ns).collect()).unwrap_or(0),
+                variance: combine(runs.iter().map(|b| b.variance).collect()).unwrap_or(0),
+                throughput: combine(runs.iter().filter_map(|b| b.throughput).collect()),
+                iterations: combine(runs.iter()
+                        .filter_map(|b| b.iterations.map(|n| n as u64))
+                        .collect())
+                    .map(|n| n as usize),
+                percentiles: combine_percentiles(runs, combine),
+            }
+        })
+        .collect()
+}
+
+/// Combines the percentiles of some runs per percentile, over the runs that
+/// report it.
+fn combine_percentiles(runs: &[Benchmark],
+                       combine: fn(Vec<u64>) -> Option<u64>)
+                       -> Option<BTreeMap<String, u64>> {
+    let mut values: BTreeMap<String, Vec<u64>> = BTreeMap::new();
+    for percentiles in runs.iter().filter_map(|b| b.percentiles.as_ref()) {
+        for (p, &ns) in percentiles {
+            values.entry(p.clone()).or_insert_with(Vec::new).push(ns);
+        }
+    }
+    if values.is_empty() {
+        return None;
+    }
+    Some(values.into_iter().filter_map(|(p, ns)| combine(ns).map(|ns| (p, ns))).collect())
+}
+
+/// Picks one run per name, in the order of `names`.
+fn pick_runs<F>(names: Vec<String>,
+                runs: BTreeMap<String, Vec<Benchmark>>,
+                pick: F)
+                -> Vec<Benchmark>
+    where F: Fn(&[Benchmark]) -> Option<&Benchmark>
+{
+    names.iter()
+        .filter_map(|name| pick(&runs[name]).cloned())
+        .collect()
+}
+
+/// Returns the median of some numbers. With an even count, the mean of the
+/// two middle numbers is rounded to the nearest integer.
+fn median(mut numbers: Vec<u64>) -> Option<u64> {
+    if numbers.is_empty() {
+        return None;
+    }
+    numbers.sort();
+    let mid = numbers.len() / 2;
+    if numbers.len() % 2 == 1 {
+        Some(numbers[mid])
+    } else {
+        mean(numbers[mid - 1..mid + 1].iter().cloned())
+    }
+}
+
+/// Returns the mean of some numbers, rounded to the nearest integer.
+fn mean<I: Iterator<Item = u64>>(numbers: I) -> Option<u64> {
+    let (sum, count) = numbers.fold((0f64, 0usize), |(sum, count), n| (sum + n as f64, count + 1));
+    if count == 0 {
+        None
+    } else {
+        Some((sum / count as f64).round() as u64)
+    }
+}
+
+/// Returns the geometric mean of the new/old ratios of the given comparisons.
+///
+/// Comparisons involving a measurement of zero are skipped. `None` is
+/// returned when there is nothing left to average.
+pub fn geomean(cmps: &[&Comparison]) -> Option<f64> {
+    geomean_by(cmps, |_| 1f64)
+}
+
+/// Like `geomean`, but weighs every ratio by its inverse variance, so that
+/// noisy benchmarks count less.
+///
+/// The variance of a ratio is estimated as the sum of the squared relative
+/// variances of its old and new measurement. A variance of zero is taken to
+/// be 1 ns, the resolution of the measurements, to keep the weights finite.
+pub fn weighted_geomean(cmps: &[&Comparison]) -> Option<f64> {
+    geomean_by(cmps, |c| {
+        let rel = |b: &Benchmark| cmp::max(b.variance, 1) as f64 / b.ns as f64;
+        1f64 / (rel(&c.old).powi(2) + rel(&c.new).powi(2))
+    })
+}
+
+/// Returns the weighted geometric mean of the new/old ratios of the given
+/// comparisons.
+fn geomean_by<F>(cmps: &[&Comparison], weight: F) -> Option<f64>
+    where F: Fn(&Comparison) -> f64
+{
+    let (mut sum, mut total) = (0f64, 0f64);
+    for c in cmps.iter().filter(|c| c.old.ns > 0 && c.new.ns > 0) {
+        let w = weight(c);
+        sum += w * (c.new.ns as f64 / c.old.ns as f64).ln();
+        total += w;
+    }
+    if total == 0f64 {
+        return None;
+    }
+    Some((sum / total).exp())
+}
+
+/// Returns the median relative variance, i.e., variance divided by ns/iter,
+/// of all old and new measurements of the comparisons. Measurements of zero
+/// ns/iter are skipped.
+pub fn noise_floor(cmps: &[Comparison]) -> Option<f64> {
+    let mut rel: Vec<f64> = cmps.iter()
+        .flat_map(|c| vec![&c.old, &c.new])
+        .filter(|b| b.ns > 0)
+        .map(|b| b.variance as f64 / b.ns as f64)
+        .collect();
+    if rel.is_empty() {
+        return None;
+    }
+    rel.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let mid = rel.len() / 2;
+    if rel.len() % 2 == 0 {
+        Some((rel[mid - 1] + rel[mid]) / 2f64)
+    } else {
+        Some(rel[mid])
+    }
+}
+
+/// Sums the old and the new benchmarks of the given comparisons into a
+/// single comparison of the totals.
+///
+/// When every benchmark reports its iteration count, the totals estimate
+/// the time the benchmarks ran in ns, and the comparison is named
+/// `total ns`. Otherwise the ns/iter are summed as they are, and the
+/// comparison is named `total ns/iter`.
+pub fn totals(cmps: &[&Comparison]) -> Comparison {
+    let timed = cmps.iter().all(|c| c.old.iterations.is_some() && c.new.iterations.is_some());
+    let sum = |bench: &Fn(&Comparison) -> &Benchmark| -> u64 {
+        cmps.iter()
+            .map(|c| {
+                let b = bench(c);
+                if timed {
+                    b.ns.saturating_mul(b.iterations.unwrap_or(1) as u64)
+                } else {
+                    b.ns
+                }
+            })
+            .fold(0, u64::saturating_add)
+    };
+    let name = if timed { "total ns" } else { "total ns/iter" };
+    let total = |ns| {
+        Benchmark {
+            name: name.to_string(),
+            ns: ns,
+            variance: 0,
+            throughput: None,
+            iterations: None,
+            percentiles: None,
+        }
+    };
+    total(sum(&|c| &c.old)).compare(total(sum(&|c| &c.new)))
+}
+
+/// Returns the rank of each of the given ns values, where the lowest value
+/// ranks first. Equal values share the best of their ranks.
+fn ranks(ns: &[u64]) -> Vec<usize> {
+    // Sort the indices once instead of counting the lower values of every
+    // value, which is quadratic in the number of benchmarks.
+    let mut order = (0..ns.len()).collect::<Vec<usize>>();
+    order.sort_by_key(|&i| ns[i]);
+    let mut ranks = vec![0; ns.len()];
+    for (pos, &i) in order.iter().enumerate() {
+        ranks[i] = if pos > 0 && ns[order[pos - 1]] == ns[i] {
+            ranks[order[pos - 1]]
+        } else {
+            pos + 1
+        };
+    }
+    ranks
+}
+
+/// Returns the index of the lowest of the given ns values, or of the highest
+/// if `bigger_is_better` as for throughputs, or `None` when that value is
+/// shared by more than one column.
+pub fn fastest(ns: &[u64], bigger_is_better: bool) -> Option<usize> {
+    let best = if bigger_is_better {
+        ns.iter().max()
+    } else {
+        ns.iter().min()
+    };
+    let best = match best {
+        Some(best) => best,
+        None => return None,
+    };
+    let mut winners = ns.iter().enumerate().filter(|&(_, n)| n == best).map(|(i, _)| i);
+    match (winners.next(), winners.next()) {
+        (Some(i), None) => Some(i),
+        _ => None,
     }
 }
 
@@ -262,39 +1155,118 @@ impl<T> Overlap<T> {
     }
 }
 
-/// Drops all commas in a string and parses it as a unsigned integer
-fn parse_commas(s: &str) -> Option<u64> {
-    drop_commas(s).parse().ok()
+/// Divides a difference by the measurement it is relative to, giving 0 when
+/// that measurement is 0. A relative change from nothing is meaningless, and
+/// a NaN or infinity would end up in the JSON and CSV output.
+fn ratio(diff: i64, base: f64) -> f64 {
+    if base == 0f64 {
+        0f64
+    } else {
+        diff as f64 / base
+    }
+}
+
+/// Formats `ns` as a factor of `base`, like `1.25x`, or as `n/a` when `base`
+/// is 0, for the same reason as `ratio`.
+fn fmt_factor(ns: u64, base: u64, precision: usize) -> String {
+    if base == 0 {
+        "n/a".to_string()
+    } else {
+        format!("{:.*}x", precision, ns as f64 / base as f64)
+    }
+}
+
+/// Drops all digit separators, commas and underscores, in a string and
+/// parses it as a unsigned integer
+fn parse_separated(s: &str) -> Option<u64> {
+    drop_separators(s).parse().ok()
 }
 
-/// Drops all commas in a string
-fn drop_commas(s: &str) -> String {
-    s.chars().filter(|&b| b != ',').collect()
+/// Drops all digit separators, commas and underscores, in a string
+fn drop_separators(s: &str) -> String {
+    s.chars().filter(|&b| b != ',' && b != '_').collect()
 }
 
-/// Commafy a number as a string.
-fn commafy(n: u64) -> String {
-    let mut with_commas = vec![];
-    let dits: Vec<u8> = n.to_string().into_bytes().into_iter().rev().collect();
-    let mut dits = &*dits;
-    loop {
-        if dits.len() < 3 {
-            with_commas.extend_from_slice(dits);
-            break;
+/// The bars of a sparkline, from lowest to highest.
+const SPARK_BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// The bars of a sparkline in ASCII, from lowest to highest.
+const SPARK_ASCII: [char; 8] = ['_', '.', ',', '-', '~', '=', '*', '#'];
+
+/// Draws a bar for every value, scaled so that the largest value is the
+/// highest bar. Zero is drawn as the lowest bar.
+fn sparkline(values: &[u64], ascii: bool) -> String {
+    let bars = if ascii { &SPARK_ASCII } else { &SPARK_BLOCKS };
+    let max = values.iter().cloned().max().unwrap_or(0);
+    values.iter()
+        .map(|&n| if max == 0 {
+            bars[0]
+        } else {
+            let level = (n as f64 / max as f64 * bars.len() as f64).ceil() as usize;
+            bars[cmp::max(level, 1) - 1]
+        })
+        .collect()
+}
+
+/// Rounds a number to `digits` significant digits, if given. Zero digits
+/// leave the number as it is.
+fn round_to(n: u64, digits: Option<usize>) -> u64 {
+    let len = n.to_string().len();
+    match digits {
+        Some(digits) if digits > 0 && len > digits => {
+            let unit = 10u64.pow((len - digits) as u32);
+            n.saturating_add(unit / 2) / unit * unit
         }
-        let piece = &dits[0..3];
-        dits = &dits[3..];
-        with_commas.extend_from_slice(piece);
-        if piece.len() == 3 && !dits.is_empty() && dits[0] != b'-' {
-            with_commas.push(b',');
+        _ => n,
+    }
+}
+
+/// Commafy a number as a string, with `sep` between every group of three
+/// digits.
+fn commafy(n: u64, sep: &str) -> String {
+    let digits = n.to_string();
+    let mut with_commas = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            with_commas.push_str(sep);
         }
+        with_commas.push(digit);
     }
-    with_commas.reverse();
-    String::from_utf8(with_commas).unwrap()
+    with_commas
+}
+
+/// Formats a throughput in MB/s in the largest unit that keeps it at least 1,
+/// with one decimal. The benchmark output reports whole MB/s, so smaller
+/// units aren't needed.
+fn fmt_throughput(mb_per_s: u64) -> String {
+    let units = ["MB/s", "GB/s", "TB/s", "PB/s"];
+    let mut value = mb_per_s as f64;
+    let mut unit = 0;
+    // Scale when the value would be printed as 1000.0 or more.
+    while value >= 999.95 && unit + 1 < units.len() {
+        value /= 1000f64;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, units[unit])
 }
 
 #[cfg(test)]
 mod tests {
+    use super::Benchmark;
+
+    /// A benchmark named b with just an ns/iter, for tests to set the other
+    /// fields of where they matter.
+    fn bench(ns: u64) -> Benchmark {
+        Benchmark {
+            name: "b".to_string(),
+            ns: ns,
+            variance: 0,
+            throughput: None,
+            iterations: None,
+            percentiles: None,
+        }
+    }
+
     mod overlap {
         use super::super::Overlap;
 
@@ -366,12 +1338,538 @@ mod tests {
         }
     }
 
+    mod paired_by {
+        use super::super::{Benchmark, Benchmarks};
+
+        quickcheck! {
+            fn pairs_on_key(old: Vec<Benchmark>, new: Vec<Benchmark>) -> bool {
+                let new = new.into_iter()
+                    .map(|mut b| {
+                        b.name = b.name.to_uppercase();
+                        b
+                    })
+                    .collect();
+                let paired = Benchmarks::from((old, new)).paired_by(|n| n.to_lowercase());
+
+                paired.comparisons()
+                    .iter()
+                    .all(|c| c.old.name.to_lowercase() == c.new.name.to_lowercase())
+            }
+        }
+    }
+
+    mod in_input_order {
+        use std::collections::BTreeSet;
+
+        use super::super::{Benchmark, Benchmarks};
+
+        quickcheck! {
+            fn follows_old_input(old: Vec<Benchmark>) -> bool {
+                let mut old = old;
+                old.retain(|b| b.ns < 1 << 40);
+                // Keep the first of each name, so its position in old is that
+                // of the benchmark it was paired from.
+                let mut seen = BTreeSet::new();
+                old.retain(|b| seen.insert(b.name.clone()));
+                let paired = Benchmarks::from((old.clone(), old.clone())).paired().in_input_order();
+                let names: Vec<&str> = old.iter().map(|b| &*b.name).collect();
+
+                paired.comparisons().windows(2).all(|w| {
+                    names.iter().position(|&n| n == w[0].old.name) <=
+                    names.iter().position(|&n| n == w[1].old.name)
+                })
+            }
+        }
+    }
+
+    mod ratio_to {
+        use super::super::{Benchmark, RatioBase};
+        use super::bench;
+
+        #[test]
+        fn bases() {
+            let c = bench(100).compare(bench(125));
+            assert_eq!(c.ratio_to(RatioBase::Old), 0.25);
+            assert_eq!(c.ratio_to(RatioBase::Old), c.diff_ratio);
+            assert_eq!(c.ratio_to(RatioBase::New), 0.2);
+            assert!((c.ratio_to(RatioBase::Geomean) - 0.2236).abs() < 1e-4);
+            let c = bench(125).compare(bench(100));
+            assert_eq!(c.ratio_to(RatioBase::New), -0.25);
+        }
+
+        quickcheck! {
+            fn geomean_is_between(old: Benchmark, new: Benchmark) -> bool {
+                if old.ns == 0 || new.ns == 0 || old.ns >= 1 << 40 || new.ns >= 1 << 40 {
+                    return true;
+                }
+                let c = old.compare(new);
+                let (a, b) = (c.ratio_to(RatioBase::Old), c.ratio_to(RatioBase::New));
+                let g = c.ratio_to(RatioBase::Geomean);
+                a.min(b) <= g + 1e-9 && g <= a.max(b) + 1e-9
+            }
+        }
+    }
+
+    mod symmetric_ratio {
+        use super::super::Benchmark;
+        use super::bench;
+
+        quickcheck! {
+            fn swapping_negates(old: Benchmark, new: Benchmark) -> bool {
+                if old.ns == 0 || new.ns == 0 || old.ns >= 1 << 40 || new.ns >= 1 << 40 {
+                    return true;
+                }
+                let forward = old.clone().compare(new.clone()).symmetric_ratio();
+                let backward = new.compare(old).symmetric_ratio();
+                forward == -backward
+            }
+        }
+
+        #[test]
+        fn doubling_and_halving() {
+            assert_eq!(bench(100).compare(bench(200)).symmetric_ratio(), 1f64);
+            assert_eq!(bench(200).compare(bench(100)).symmetric_ratio(), -1f64);
+        }
+    }
+
+    mod reduce {
+        use super::super::{reduce, Benchmark, Reduce};
+        use super::bench;
+
+        /// A run of a benchmark, with a variance of a tenth of its ns/iter.
+        fn run(ns: u64) -> Benchmark {
+            Benchmark { variance: ns / 10, ..bench(ns) }
+        }
+
+        quickcheck! {
+            fn duplicated_runs_unchanged(benches: Vec<Benchmark>) -> bool {
+                let mut benches = benches;
+                benches.sort();
+                benches.dedup();
+                benches.retain(|b| b.ns < 1 << 40 && b.variance < 1 << 40);
+                let mut runs = benches.clone();
+                runs.extend(benches.clone());
+
+                [Reduce::Min, Reduce::Max, Reduce::Median, Reduce::Mean].iter().all(|&r| {
+                    reduce(runs.clone(), r).into_iter().zip(benches.clone()).all(|(a, b)| {
+                        a.name == b.name && a.ns == b.ns && a.variance == b.variance
+                    })
+                })
+            }
+        }
+
+        #[test]
+        fn reducers() {
+            let runs = vec![run(300), run(100), run(1000), run(200)];
+            let ns = |r| reduce(runs.clone(), r).iter().map(|b| b.ns).collect::<Vec<_>>();
+            assert_eq!(ns(Reduce::Min), vec![100]);
+            assert_eq!(ns(Reduce::Max), vec![1000]);
+            assert_eq!(ns(Reduce::Median), vec![250]);
+            assert_eq!(ns(Reduce::Mean), vec![400]);
+        }
+
+        #[test]
+        fn min_keeps_the_fastest_run() {
+            let mut fast = run(100);
+            fast.variance = 50;
+            let min = reduce(vec![run(300), fast, run(200)], Reduce::Min);
+            assert_eq!((min[0].ns, min[0].variance), (100, 50));
+        }
+
+        #[test]
+        fn combines_percentiles() {
+            let with_p95 = |ns: u64| {
+                let mut b = run(ns);
+                b.percentiles = Some(vec![("p95".to_string(), ns * 2)].into_iter().collect());
+                b
+            };
+            let mean = reduce(vec![with_p95(100), run(200), with_p95(300)], Reduce::Mean);
+            let at_p95 = mean[0].clone().at_percentile("p95").unwrap();
+            assert_eq!(at_p95.ns, 400);
+            assert!(mean[0].clone().at_percentile("p99").is_none());
+        }
+    }
+
+    mod geomean {
+        use super::super::{geomean, weighted_geomean, Benchmark};
+        use super::bench;
+
+        quickcheck! {
+            fn unchanged_is_one(benches: Vec<Benchmark>) -> bool {
+                let cmps: Vec<_> = benches.into_iter().map(|b| b.clone().compare(b)).collect();
+                let cmps: Vec<_> = cmps.iter().collect();
+
+                geomean(&cmps).map_or(true, |ratio| (ratio - 1f64).abs() < 1e-9)
+            }
+
+            fn doubled_is_two(benches: Vec<Benchmark>) -> bool {
+                let cmps: Vec<_> = benches.into_iter()
+                    .filter(|b| b.ns < 1 << 40)
+                    .map(|b| {
+                        let mut new = b.clone();
+                        new.ns *= 2;
+                        b.compare(new)
+                    })
+                    .collect();
+                let cmps: Vec<_> = cmps.iter().collect();
+
+                geomean(&cmps).map_or(true, |ratio| (ratio - 2f64).abs() < 1e-9) &&
+                weighted_geomean(&cmps).map_or(true, |ratio| (ratio - 2f64).abs() < 1e-9)
+            }
+        }
+
+        #[test]
+        fn noisy_counts_less() {
+            // A steady benchmark that doubled and a noisy one that halved.
+            let steady = bench(1000).compare(bench(2000));
+            let noisy = Benchmark { variance: 500, ..bench(1000) }
+                .compare(Benchmark { variance: 500, ..bench(500) });
+            let cmps = vec![&steady, &noisy];
+
+            assert!((geomean(&cmps).unwrap() - 1f64).abs() < 1e-9);
+            assert!(weighted_geomean(&cmps).unwrap() > 1.9);
+        }
+    }
+
+    mod direction {
+        use std::str::FromStr;
+
+        use super::super::{Benchmark, Direction};
+
+        quickcheck! {
+            fn faster_is_improvement(b: Benchmark) -> bool {
+                if b.ns >= 1 << 40 {
+                    return true;
+                }
+                let mut new = b.clone();
+                new.ns = b.ns / 2;
+                let c = b.compare(new);
+                let expected = if c.diff_ns == 0 {
+                    Direction::Neutral
+                } else {
+                    Direction::Improvement
+                };
+                c.direction(false) == expected && c.to_record(false).change_pct <= 0f64
+            }
+
+            fn zero_ns_is_zero_pct(old: Benchmark, new: Benchmark) -> bool {
+                let mut old = old;
+                old.ns = 0;
+                let record = old.compare(new).to_record(false);
+                record.change_pct == 0f64
+            }
+
+            fn bigger_is_better_flips(old: Benchmark, new: Benchmark) -> bool {
+                if old.ns >= 1 << 40 || new.ns >= 1 << 40 {
+                    return true;
+                }
+                let c = old.compare(new);
+                match c.direction(false) {
+                    Direction::Improvement => c.direction(true) == Direction::Regression,
+                    Direction::Regression => c.direction(true) == Direction::Improvement,
+                    Direction::Neutral => c.direction(true) == Direction::Neutral,
+                }
+            }
+        }
+
+        #[test]
+        fn names() {
+            assert_eq!(Direction::Improvement.name(), "improvement");
+            assert_eq!(Direction::Regression.name(), "regression");
+            assert_eq!(Direction::Neutral.name(), "neutral");
+        }
+
+        #[test]
+        fn more_throughput_is_improvement() {
+            let mut c = Benchmark::from_str("test a ... bench: 100 ns/iter (+/- 1)")
+                .unwrap()
+                .compare(Benchmark::from_str("test a ... bench: 200 ns/iter (+/- 1)").unwrap());
+            assert_eq!(c.direction(false), Direction::Regression);
+            c.by_throughput = true;
+            assert_eq!(c.direction(false), Direction::Improvement);
+        }
+    }
+
+    mod throughput_change {
+        use super::super::Benchmark;
+        use super::bench;
+
+        #[test]
+        fn relative_change() {
+            let mb_s = |throughput| Benchmark { throughput: throughput, ..bench(1000) };
+            assert_eq!(mb_s(Some(100)).compare(mb_s(Some(150))).throughput_change(),
+                       Some(0.5));
+            assert_eq!(mb_s(Some(100)).compare(mb_s(Some(50))).throughput_change(),
+                       Some(-0.5));
+            assert_eq!(mb_s(None).compare(mb_s(Some(50))).throughput_change(), None);
+            assert_eq!(mb_s(Some(0)).compare(mb_s(Some(50))).throughput_change(), None);
+        }
+    }
+
+    mod variance_ratio {
+        use super::super::Benchmark;
+        use super::bench;
+
+        #[test]
+        fn same_mean_noisier() {
+            let noise = |variance| Benchmark { variance: variance, ..bench(1000) };
+            assert_eq!(noise(10).compare(noise(30)).variance_ratio(), Some(3f64));
+            assert_eq!(noise(30).compare(noise(15)).variance_ratio(), Some(0.5f64));
+        }
+
+        #[test]
+        fn zero_old_variance() {
+            assert_eq!(bench(1000).compare(bench(1000)).variance_ratio(), Some(1f64));
+            let noisy = Benchmark { variance: 5, ..bench(1000) };
+            assert_eq!(bench(1000).compare(noisy).variance_ratio(), None);
+        }
+    }
+
+    mod column {
+        use super::super::Column;
+
+        #[test]
+        fn names_parse() {
+            for name in Column::names() {
+                assert!(name.parse::<Column>().is_ok());
+            }
+            assert!("bogus".parse::<Column>().is_err());
+        }
+    }
+
+    mod to_row {
+        use super::super::{Column, Comparison, RowFormat};
+        use super::bench;
+
+        fn cells(c: &Comparison, columns: Vec<Column>, relative: bool) -> Vec<String> {
+            let fmt = RowFormat {
+                columns: columns,
+                variance: false,
+                bounds: false,
+                iterations: false,
+                precision: 2,
+                round: None,
+                separator: ",".to_string(),
+                relative: relative,
+                arrows: false,
+                ascii: false,
+            };
+            let row = c.to_row(&fmt, "");
+            (0..row.len()).map(|i| row.get_cell(i).unwrap().get_content()).collect()
+        }
+
+        #[test]
+        fn relative_to_zero_old() {
+            let columns = vec![Column::Fst, Column::Snd];
+            assert_eq!(cells(&bench(100).compare(bench(125)), columns.clone(), true),
+                       vec!["1.00x", "1.25x"]);
+            assert_eq!(cells(&bench(0).compare(bench(125)), columns, true),
+                       vec!["n/a", "n/a"]);
+        }
+
+        #[test]
+        fn speedup_of_zero_new() {
+            assert_eq!(cells(&bench(200).compare(bench(100)), vec![Column::Speedup], false),
+                       vec!["2.00x"]);
+            assert_eq!(cells(&bench(200).compare(bench(0)), vec![Column::Speedup], false),
+                       vec!["n/a"]);
+        }
+    }
+
+    mod to_influx {
+        use super::super::Benchmark;
+        use super::bench;
+
+        #[test]
+        fn escapes_tags() {
+            let b = Benchmark {
+                name: "parse a, b".to_string(),
+                variance: 56,
+                ..bench(1234)
+            };
+            let tags = vec![("commit".to_string(), "abc123".to_string())];
+            assert_eq!(b.to_influx(&tags, 1500000000000000000),
+                       "bench,name=parse\\ a\\,\\ b,commit=abc123 ns=1234,variance=56 \
+                        1500000000000000000");
+        }
+    }
+
+    mod round_to {
+        use super::super::round_to;
+
+        #[test]
+        fn significant_digits() {
+            assert_eq!(round_to(112957, Some(3)), 113000);
+            assert_eq!(round_to(112957, Some(2)), 110000);
+            assert_eq!(round_to(349, Some(3)), 349);
+            assert_eq!(round_to(349, Some(5)), 349);
+            assert_eq!(round_to(999, Some(2)), 1000);
+            assert_eq!(round_to(112957, Some(0)), 112957);
+            assert_eq!(round_to(112957, None), 112957);
+        }
+    }
+
+    mod csv_quote {
+        use super::super::csv_quote;
+
+        #[test]
+        fn quotes_when_needed() {
+            assert_eq!(csv_quote("ac_one_byte"), "ac_one_byte");
+            assert_eq!(csv_quote("bench<Vec<u8>, 16>"), "\"bench<Vec<u8>, 16>\"");
+            assert_eq!(csv_quote("say \"hi\""), "\"say \"\"hi\"\"\"");
+        }
+    }
+
+    mod trends {
+        use super::super::{trends, Record};
+
+        fn record(name: &str, change_pct: f64) -> Record {
+            Record {
+                name: name.to_string(),
+                old_ns: 100,
+                new_ns: 100,
+                old_variance: 0,
+                new_variance: 0,
+                diff_ns: 0,
+                change_pct: change_pct,
+                direction: "neutral".to_string(),
+            }
+        }
+
+        #[test]
+        fn pairs_by_name() {
+            let trends = trends(vec![record("b", 10f64), record("a", -5f64)],
+                                vec![record("c", 1f64), record("b", 4f64)]);
+            let names = trends.iter().map(|t| &*t.name).collect::<Vec<&str>>();
+            assert_eq!(names, vec!["a", "b", "c"]);
+            assert!(trends[0].old.is_some() && trends[0].new.is_none());
+            assert_eq!(trends[0].change_pct_diff, None);
+            assert_eq!(trends[1].change_pct_diff, Some(-6f64));
+            assert!(trends[2].old.is_none() && trends[2].new.is_some());
+        }
+
+        #[test]
+        fn rounds_to_hundredths() {
+            let trends = trends(vec![record("b", 84.49511845429099)],
+                                vec![record("b", -45.79802390556194)]);
+            assert_eq!(trends[0].old.as_ref().unwrap().change_pct, 84.5);
+            assert_eq!(trends[0].new.as_ref().unwrap().change_pct, -45.8);
+            assert_eq!(trends[0].change_pct_diff, Some(-130.29));
+        }
+    }
+
+    mod noise_floor {
+        use super::super::{noise_floor, Benchmark};
+        use super::bench;
+
+        #[test]
+        fn median_of_relative_variances() {
+            let noisy = |ns, variance| Benchmark { variance: variance, ..bench(ns) };
+            let cmps = vec![noisy(100, 1).compare(noisy(100, 2)),
+                            noisy(1000, 30).compare(bench(0)),
+                            noisy(10, 5).compare(noisy(200, 8))];
+            // 0.01, 0.02, 0.03, 0.04 and 0.5
+            assert_eq!(noise_floor(&cmps), Some(0.03));
+            assert_eq!(noise_floor(&cmps[..1]), Some(0.015));
+            assert_eq!(noise_floor(&[]), None);
+        }
+    }
+
+    mod ranks {
+        use super::super::ranks;
+
+        #[test]
+        fn ties_share_a_rank() {
+            assert_eq!(ranks(&[300, 100, 200, 100]), vec![4, 1, 3, 1]);
+            assert_eq!(ranks(&[]), Vec::<usize>::new());
+        }
+
+        quickcheck! {
+            fn one_more_than_the_lower_values(ns: Vec<u64>) -> bool {
+                let lower = |n: &u64| ns.iter().filter(|&m| m < n).count();
+                ranks(&ns) == ns.iter().map(|n| 1 + lower(n)).collect::<Vec<usize>>()
+            }
+        }
+    }
+
+    mod fastest {
+        use super::super::fastest;
+
+        quickcheck! {
+            fn is_strictly_lowest(ns: Vec<u64>) -> bool {
+                match fastest(&ns, false) {
+                    Some(i) => ns.iter().enumerate().all(|(j, &n)| j == i || n > ns[i]),
+                    None => ns.iter().min().map_or(true, |min| {
+                        ns.iter().filter(|&n| n == min).count() > 1
+                    }),
+                }
+            }
+
+            fn is_strictly_highest_if_bigger_is_better(ns: Vec<u64>) -> bool {
+                match fastest(&ns, true) {
+                    Some(i) => ns.iter().enumerate().all(|(j, &n)| j == i || n < ns[i]),
+                    None => ns.iter().max().map_or(true, |max| {
+                        ns.iter().filter(|&n| n == max).count() > 1
+                    }),
+                }
+            }
+        }
+
+        #[test]
+        fn tie_has_no_winner() {
+            assert_eq!(fastest(&[3, 3], false), None);
+            assert_eq!(fastest(&[4, 3], false), Some(1));
+            assert_eq!(fastest(&[4, 3], true), Some(0));
+        }
+    }
+
+    mod totals {
+        use super::super::{totals, Benchmark};
+        use super::bench;
+
+        #[test]
+        fn sums_ns_per_iter() {
+            let iters = |ns, n| Benchmark { iterations: Some(n), ..bench(ns) };
+            let a = iters(100, 10).compare(iters(200, 10));
+            let b = bench(1000).compare(bench(500));
+            let total = totals(&[&a, &b]);
+            assert_eq!(total.old.name, "total ns/iter");
+            assert_eq!((total.old.ns, total.new.ns), (1100, 700));
+        }
+
+        #[test]
+        fn estimates_time_with_iterations() {
+            let iters = |ns, n| Benchmark { iterations: Some(n), ..bench(ns) };
+            let a = iters(100, 10).compare(iters(200, 5));
+            let b = iters(1000, 2).compare(iters(500, 2));
+            let total = totals(&[&a, &b]);
+            assert_eq!(total.old.name, "total ns");
+            assert_eq!((total.old.ns, total.new.ns), (3000, 2000));
+        }
+    }
+
+    mod sparkline {
+        use super::super::sparkline;
+
+        #[test]
+        fn scales_to_the_largest() {
+            assert_eq!(sparkline(&[50, 100], false), "▄█");
+            assert_eq!(sparkline(&[100, 1], false), "█▁");
+            assert_eq!(sparkline(&[0, 0], false), "▁▁");
+        }
+
+        #[test]
+        fn ascii_bars() {
+            assert_eq!(sparkline(&[50, 100], true), "-#");
+        }
+    }
+
     mod commafy {
         use super::super::commafy;
 
         quickcheck! {
             fn comma_every_three(n: u64) -> bool {
-                let commafied = commafy(n);
+                let commafied = commafy(n, ",");
                 let mut commafied = commafied.split(',');
                 let s = commafied.next().unwrap();
                 if s.len() == 0 || s.len() > 3 {
@@ -386,22 +1884,52 @@ mod tests {
             }
 
             fn number_matches(n: u64) -> bool {
-                let commafied = commafy(n);
+                let commafied = commafy(n, ",");
                 let formatted = format!("{}", n);
                 let stripped: String = commafied.chars().filter(|&b| b != ',').collect();
                 formatted == stripped
             }
+
+            fn any_separator(n: u64) -> bool {
+                commafy(n, "\u{2009}") == commafy(n, ",").replace(',', "\u{2009}")
+            }
+        }
+
+        #[test]
+        fn separators() {
+            assert_eq!(commafy(1234567, "."), "1.234.567");
+            assert_eq!(commafy(123456, " "), "123 456");
+            assert_eq!(commafy(999, "."), "999");
+        }
+    }
+
+    mod fmt_throughput {
+        use super::super::fmt_throughput;
+
+        #[test]
+        fn scales_at_boundaries() {
+            assert_eq!(fmt_throughput(0), "0.0 MB/s");
+            assert_eq!(fmt_throughput(88), "88.0 MB/s");
+            assert_eq!(fmt_throughput(999), "999.0 MB/s");
+            assert_eq!(fmt_throughput(1000), "1.0 GB/s");
+            assert_eq!(fmt_throughput(12000), "12.0 GB/s");
+            assert_eq!(fmt_throughput(28653), "28.7 GB/s");
+            assert_eq!(fmt_throughput(999_949), "999.9 GB/s");
+            assert_eq!(fmt_throughput(999_999), "1.0 TB/s");
+            assert_eq!(fmt_throughput(1_000_000), "1.0 TB/s");
+            assert_eq!(fmt_throughput(5_000_000_000_000), "5000.0 PB/s");
         }
     }
 
     mod benchmark {
-        use super::super::Benchmark;
+        use super::super::{lacks_variance, Benchmark};
         use quickcheck::Arbitrary;
         use quickcheck::Gen;
 
         impl Arbitrary for Benchmark {
             fn arbitrary<G: Gen>(g: &mut G) -> Self {
-                let (ns, variance, throughput): (u64, u64, Option<u64>) = Arbitrary::arbitrary(g);
+                let (ns, variance, throughput, iterations): (u64, u64, Option<u64>, Option<usize>) =
+                    Arbitrary::arbitrary(g);
                 let name = {
                     let size = g.size();
                     let size = g.gen_range(1, size);
@@ -412,13 +1940,15 @@ mod tests {
                     ns: ns,
                     variance: variance,
                     throughput: throughput,
+                    iterations: iterations,
+                    percentiles: None,
                 }
             }
         }
 
         fn deep_eq(b1: &Benchmark, b2: &Benchmark) -> bool {
             b1.name == b2.name && b1.variance == b2.variance && b1.ns == b2.ns &&
-            b1.throughput == b2.throughput
+            b1.throughput == b2.throughput && b1.iterations == b2.iterations
         }
 
         fn as_string(b: &Benchmark) -> String {
@@ -426,13 +1956,81 @@ mod tests {
                               b.name,
                               b.ns,
                               b.variance);
-            if let Some(throughput) = b.throughput {
+            let res = if let Some(throughput) = b.throughput {
                 format!("{} = {} MB/s", res, throughput)
             } else {
                 res
+            };
+            if let Some(iterations) = b.iterations {
+                format!("{} ({} iters)", res, iterations)
+            } else {
+                res
+            }
+        }
+
+        #[test]
+        fn generic_names() {
+            for name in &["mycrate::bench<Vec<u8>, 16>",
+                          "bench::<HashMap<String, Vec<u8>>>",
+                          "sort<[u64; 1024]>",
+                          "fold<(u8, u16), 4>"] {
+                let line = format!("test {} ... bench: 1,234 ns/iter (+/- 56)", name);
+                let bench: Benchmark = line.parse().unwrap();
+                assert_eq!(&bench.name, name);
+                assert_eq!(bench.ns, 1234);
             }
         }
 
+        #[test]
+        fn underscore_separators() {
+            let line = "test b ... bench: 1_234_567 ns/iter (+/- 8_901) = 1_024 MB/s (1_000 iters)";
+            let bench: Benchmark = line.parse().unwrap();
+            assert_eq!((bench.ns, bench.variance, bench.throughput, bench.iterations),
+                       (1234567, 8901, Some(1024), Some(1000)));
+            let line = "test b ... bench: 2_314 MB/s (+/- 1_2)";
+            let bench = Benchmark::parse_throughput(line).unwrap();
+            assert_eq!((bench.ns, bench.variance), (2314, 12));
+        }
+
+        #[test]
+        fn annotations() {
+            for line in &["test b - should panic ... bench: 1,234 ns/iter (+/- 56)",
+                          "test b should_panic ... bench: 1,234 ns/iter (+/- 56)",
+                          "test b ignored ... bench: 1,234 ns/iter (+/- 56)",
+                          "test b (ignored) - should panic ... bench: 1,234 ns/iter (+/- 56)"] {
+                let bench: Benchmark = line.parse().unwrap();
+                assert_eq!((bench.name.as_str(), bench.ns, bench.variance), ("b", 1234, 56));
+            }
+            let line = "test b (ignored) ... bench: 2,314 MB/s (+/- 12)";
+            let bench = Benchmark::parse_throughput(line).unwrap();
+            assert_eq!((bench.name.as_str(), bench.ns), ("b", 2314));
+        }
+
+        #[test]
+        fn malformed_variance() {
+            for line in &["test b ... bench: 1,234 ns/iter (+/- NaN)",
+                          "test b ... bench: 1,234 ns/iter (+/- garbage) = 12 MB/s",
+                          "test b ... bench: 1,234 ns/iter"] {
+                let bench: Benchmark = line.parse().unwrap();
+                assert_eq!(bench.ns, 1234);
+                assert_eq!(bench.variance, 0);
+                assert!(lacks_variance(line));
+            }
+            assert!(!lacks_variance("test b ... bench: 1,234 ns/iter (+/- 56)"));
+        }
+
+        #[test]
+        fn throughput_lines() {
+            let line = "test b ... bench: 2,314 MB/s (+/- 12)";
+            let bench = Benchmark::parse_throughput(line).unwrap();
+            assert_eq!((bench.ns, bench.variance, bench.throughput), (2314, 12, None));
+            let line = "test b ... bench: 1,234 ns/iter (+/- 56) = 100 MB/s";
+            let bench = Benchmark::parse_throughput(line).unwrap();
+            assert_eq!((bench.ns, bench.variance, bench.throughput), (100, 0, None));
+            assert!(Benchmark::parse_throughput("test b ... bench: 1,234 ns/iter (+/- 56)")
+                .is_none());
+        }
+
         quickcheck! {
             fn reparse(b1: Benchmark) -> bool {
                 if let Ok(b2) = as_string(&b1).parse() {
@@ -441,6 +2039,10 @@ mod tests {
                     false
                 }
             }
+
+            fn display_reparses(b1: Benchmark) -> bool {
+                b1.to_string().parse().ok().map_or(false, |b2| deep_eq(&b1, &b2))
+            }
         }
     }
 }
@@ -0,0 +1,120 @@
+use std::io::Read;
+use std::path::Path;
+
+use rustc_serialize::json::Json;
+
+use error::{Result, Error};
+use open_file;
+
+/// The config file that is read from the current directory when no
+/// --config is given.
+pub const DEFAULT_CONFIG: &'static str = ".benchcmp.json";
+
+/// A flag from the config file, with its value if it takes one.
+pub type Flag = (String, Option<String>);
+
+/// Reads the default flags from a config file.
+///
+/// The config file is a JSON object mapping long flag names (without the
+/// leading dashes) to values. A flag set to `true` is passed without a
+/// value, a flag set to `false` or `null` is left out, and numbers and
+/// strings are passed as the value of the flag. For example:
+///
+/// ```json
+/// { "threshold": 5, "variance": true, "color": "never" }
+/// ```
+///
+/// When `path` is `None`, the default config file is read if it exists.
+pub fn read_defaults(path: Option<&str>) -> Result<Vec<Flag>> {
+    let path = match path {
+        Some(path) => path,
+        None if Path::new(DEFAULT_CONFIG).is_file() => DEFAULT_CONFIG,
+        None => return Ok(vec![]),
+    };
+    let mut config = String::new();
+    try!(try!(open_file(path)).read_to_string(&mut config));
+    let config = try!(Json::from_str(&config).map_err(|e| Error::Config {
+        path: path.into(),
+        msg: e.to_string(),
+    }));
+    let flags = match config {
+        Json::Object(flags) => flags,
+        _ => {
+            return Err(Error::Config {
+                path: path.into(),
+                msg: "expected an object mapping flag names to values".to_string(),
+            })
+        }
+    };
+    let mut defaults = vec![];
+    for (name, value) in flags {
+        let flag = format!("--{}", name);
+        match value {
+            Json::Boolean(true) => defaults.push((flag, None)),
+            Json::Boolean(false) | Json::Null => {}
+            Json::I64(n) => defaults.push((flag, Some(n.to_string()))),
+            Json::U64(n) => defaults.push((flag, Some(n.to_string()))),
+            Json::F64(n) => defaults.push((flag, Some(n.to_string()))),
+            Json::String(s) => defaults.push((flag, Some(s))),
+            Json::Array(_) | Json::Object(_) => {
+                return Err(Error::Config {
+                    path: path.into(),
+                    msg: format!("the value of {} is not a boolean, number or string", name),
+                })
+            }
+        }
+    }
+    Ok(defaults)
+}
+
+/// Adds the default flags to the command line arguments, leaving out those
+/// that are already given on the command line.
+pub fn with_defaults(mut argv: Vec<String>, defaults: Vec<Flag>) -> Vec<String> {
+    let mut extra = vec![];
+    for (flag, value) in defaults {
+        let prefix = format!("{}=", flag);
+        if argv.iter().any(|arg| *arg == flag || arg.starts_with(&prefix)) {
+            continue;
+        }
+        extra.push(flag);
+        extra.extend(value);
+    }
+    // Insert after the program name and the benchcmp subcommand.
+    let at = if argv.len() < 2 { argv.len() } else { 2 };
+    let rest = argv.split_off(at);
+    argv.extend(extra);
+    argv.extend(rest);
+    argv
+}
+
+#[cfg(test)]
+mod tests {
+    mod with_defaults {
+        use super::super::with_defaults;
+
+        fn strings(strs: &[&str]) -> Vec<String> {
+            strs.iter().map(|s| s.to_string()).collect()
+        }
+
+        fn defaults() -> Vec<(String, Option<String>)> {
+            vec![("--threshold".to_string(), Some("5".to_string())),
+                 ("--variance".to_string(), None)]
+        }
+
+        #[test]
+        fn adds_missing_flags() {
+            let argv = strings(&["cargo-benchcmp", "benchcmp", "old", "new"]);
+            assert_eq!(with_defaults(argv, defaults()),
+                       strings(&["cargo-benchcmp", "benchcmp", "--threshold", "5", "--variance",
+                                 "old", "new"]));
+        }
+
+        #[test]
+        fn command_line_overrides() {
+            let argv = strings(&["cargo-benchcmp", "benchcmp", "--threshold=10", "old", "new"]);
+            assert_eq!(with_defaults(argv, defaults()),
+                       strings(&["cargo-benchcmp", "benchcmp", "--variance", "--threshold=10",
+                                 "old", "new"]));
+        }
+    }
+}
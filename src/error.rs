@@ -5,6 +5,10 @@ use std::path::PathBuf;
 use std::result;
 
 use regex;
+use rustc_serialize::json;
+
+use benchmark::Column;
+use Format;
 
 pub type Result<T> = result::Result<T, Error>;
 
@@ -12,7 +16,71 @@ pub type Result<T> = result::Result<T, Error>;
 pub enum Error {
     Regex(regex::Error),
     Io(io::Error),
+    Output(io::Error),
+    JsonDecode(json::DecoderError),
+    JsonEncode(json::EncoderError),
     OpenFile { path: PathBuf, err: io::Error },
+    UnknownBaseline(String),
+    NoBenchmarks(String),
+    NoHistory(String),
+    NoClipboard,
+    WatchStdin,
+    Config { path: PathBuf, msg: String },
+    RenameMap { path: PathBuf, line: usize },
+    JsonReport { source: String, msg: String },
+    UnknownColumn(String),
+    InvalidEmit(String),
+    InvalidTag(String),
+    Regressions { count: usize, threshold: u8 },
+    Regressed(usize),
+    NoisierVariance { count: usize, threshold: u32 },
+    MissingRequired(Vec<String>),
+    InvalidBudget(String),
+    OverBudget(usize),
+    Failures(Vec<Error>),
+}
+
+impl Error {
+    /// Returns the exit status for the error: 1 if a check on the comparisons
+    /// failed, 2 for invalid options, 3 for inputs that can't be read and 4
+    /// for output that can't be written.
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            Error::Regressions { .. } |
+            Error::Regressed(_) |
+            Error::NoisierVariance { .. } |
+            Error::MissingRequired(_) |
+            Error::OverBudget(_) |
+            Error::Failures(_) => 1,
+            Error::Regex(_) |
+            Error::UnknownBaseline(_) |
+            Error::WatchStdin |
+            Error::Config { .. } |
+            Error::RenameMap { .. } |
+            Error::UnknownColumn(_) |
+            Error::InvalidEmit(_) |
+            Error::InvalidTag(_) |
+            Error::InvalidBudget(_) => 2,
+            Error::Io(_) |
+            Error::JsonDecode(_) |
+            Error::OpenFile { .. } |
+            Error::NoBenchmarks(_) |
+            Error::NoHistory(_) |
+            Error::JsonReport { .. } => 3,
+            Error::Output(_) |
+            Error::JsonEncode(_) |
+            Error::NoClipboard => 4,
+        }
+    }
+
+    /// Turns an I/O error into an `Output` error, for the errors of writing
+    /// the output as opposed to reading the inputs.
+    pub fn in_output(self) -> Error {
+        match self {
+            Error::Io(err) => Error::Output(err),
+            err => err,
+        }
+    }
 }
 
 impl error::Error for Error {
@@ -20,16 +88,58 @@ impl error::Error for Error {
         match *self {
             Error::Regex(ref err) => err.description(),
             Error::Io(ref err) => err.description(),
+            Error::Output(ref err) => err.description(),
+            Error::JsonDecode(ref err) => err.description(),
+            Error::JsonEncode(ref err) => err.description(),
             Error::OpenFile { ref err, .. } => err.description(),
+            Error::UnknownBaseline(_) => "unknown baseline",
+            Error::NoBenchmarks(_) => "no benchmarks found",
+            Error::NoHistory(_) => "no history directory",
+            Error::NoClipboard => "no clipboard available",
+            Error::WatchStdin => "cannot watch stdin",
+            Error::Config { .. } => "invalid config file",
+            Error::RenameMap { .. } => "invalid rename map",
+            Error::JsonReport { .. } => "invalid JSON report",
+            Error::UnknownColumn(_) => "unknown column",
+            Error::InvalidEmit(_) => "invalid --emit",
+            Error::InvalidTag(_) => "invalid --tag",
+            Error::Regressions { .. } => "benchmarks regressed",
+            Error::Regressed(_) => "benchmarks regressed",
+            Error::NoisierVariance { .. } => "benchmarks got noisier",
+            Error::MissingRequired(_) => "required benchmarks missing",
+            Error::InvalidBudget(_) => "invalid --budget",
+            Error::OverBudget(_) => "benchmarks over budget",
+            Error::Failures(_) => "checks failed",
         }
     }
 
     fn cause(&self) -> Option<&error::Error> {
-        Some(match *self {
-            Error::Regex(ref err) => err,
-            Error::Io(ref err) => err,
-            Error::OpenFile { ref err, .. } => err,
-        })
+        match *self {
+            Error::Regex(ref err) => Some(err),
+            Error::Io(ref err) => Some(err),
+            Error::Output(ref err) => Some(err),
+            Error::JsonDecode(ref err) => Some(err),
+            Error::JsonEncode(ref err) => Some(err),
+            Error::OpenFile { ref err, .. } => Some(err),
+            Error::UnknownBaseline(_) => None,
+            Error::NoBenchmarks(_) => None,
+            Error::NoHistory(_) => None,
+            Error::NoClipboard => None,
+            Error::WatchStdin => None,
+            Error::Config { .. } => None,
+            Error::RenameMap { .. } => None,
+            Error::JsonReport { .. } => None,
+            Error::UnknownColumn(_) => None,
+            Error::InvalidEmit(_) => None,
+            Error::InvalidTag(_) => None,
+            Error::Regressions { .. } => None,
+            Error::Regressed(_) => None,
+            Error::NoisierVariance { .. } => None,
+            Error::MissingRequired(_) => None,
+            Error::InvalidBudget(_) => None,
+            Error::OverBudget(_) => None,
+            Error::Failures(_) => None,
+        }
     }
 }
 
@@ -38,7 +148,73 @@ impl fmt::Display for Error {
         match *self {
             Error::Regex(ref err) => err.fmt(f),
             Error::Io(ref err) => err.fmt(f),
+            Error::Output(ref err) => write!(f, "cannot write output: {}", err),
+            Error::JsonDecode(ref err) => err.fmt(f),
+            Error::JsonEncode(ref err) => err.fmt(f),
             Error::OpenFile { ref path, ref err } => write!(f, "{}: {}", err, path.display()),
+            Error::UnknownBaseline(ref baseline) => {
+                write!(f, "baseline {} is not <old>, <new> or best", baseline)
+            }
+            Error::NoBenchmarks(ref source) => write!(f, "no benchmarks found in {}", source),
+            Error::NoHistory(ref old) => {
+                write!(f, "--history needs <old> to be a directory of runs, not {}", old)
+            }
+            Error::NoClipboard => {
+                write!(f,
+                       "no clipboard available: --clipboard needs one of pbcopy, clip, \
+                        wl-copy, xclip or xsel")
+            }
+            Error::WatchStdin => write!(f, "--watch needs input files, it cannot watch stdin"),
+            Error::Config { ref path, ref msg } => {
+                write!(f, "invalid config file {}: {}", path.display(), msg)
+            }
+            Error::JsonReport { ref source, ref msg } => {
+                write!(f, "invalid JSON report {}: {}", source, msg)
+            }
+            Error::RenameMap { ref path, line } => {
+                write!(f,
+                       "invalid rename map {}: line {} is not of the form old_name=new_name",
+                       path.display(),
+                       line)
+            }
+            Error::UnknownColumn(ref column) => {
+                write!(f,
+                       "unknown column {}, expected one of: {}",
+                       column,
+                       Column::names().join(", "))
+            }
+            Error::InvalidEmit(ref emit) => {
+                write!(f,
+                       "invalid --emit {}, expected <format>:<path> with one of the formats: {}",
+                       emit,
+                       Format::names().join(", "))
+            }
+            Error::InvalidTag(ref tag) => {
+                write!(f, "invalid --tag {}, expected <key>=<value>", tag)
+            }
+            Error::Regressions { count, threshold } => {
+                write!(f, "{} benchmarks regressed by {}% or more", count, threshold)
+            }
+            Error::Regressed(count) => write!(f, "{} benchmarks regressed", count),
+            Error::NoisierVariance { count, threshold } => {
+                write!(f,
+                       "the variance of {} benchmarks grew by {}% or more",
+                       count,
+                       threshold)
+            }
+            Error::MissingRequired(ref names) => {
+                write!(f, "required benchmarks not in both old and new: {}", names.join(", "))
+            }
+            Error::InvalidBudget(ref budget) => {
+                write!(f,
+                       "invalid --budget {}, expected <name>=<ns> with a positive ns",
+                       budget)
+            }
+            Error::OverBudget(count) => write!(f, "{} benchmarks are over budget", count),
+            Error::Failures(ref failures) => {
+                let lines = failures.iter().map(|e| e.to_string()).collect::<Vec<String>>();
+                write!(f, "{}", lines.join("\n"))
+            }
         }
     }
 }
@@ -54,3 +230,15 @@ impl From<io::Error> for Error {
         Error::Io(err)
     }
 }
+
+impl From<json::DecoderError> for Error {
+    fn from(err: json::DecoderError) -> Error {
+        Error::JsonDecode(err)
+    }
+}
+
+impl From<json::EncoderError> for Error {
+    fn from(err: json::EncoderError) -> Error {
+        Error::JsonEncode(err)
+    }
+}
@@ -2,27 +2,44 @@ extern crate rustc_serialize;
 extern crate docopt;
 #[macro_use]
 extern crate lazy_static;
+extern crate libc;
 extern crate regex;
-#[macro_use]
 extern crate prettytable;
 #[cfg(test)]
 #[macro_use]
 extern crate quickcheck;
 
-use std::io::{self, BufRead};
-use std::fs::File;
+use std::cmp::{self, Ordering};
+use std::collections::BTreeMap;
+use std::env;
+use std::f64;
+use std::io::{self, BufRead, Read, Write};
+use std::fs::{self, File};
+use std::mem;
 use std::path::{Path, PathBuf};
 use std::process;
+use std::result;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use docopt::Docopt;
+use rustc_serialize::{json, Decodable, Decoder};
+use rustc_serialize::json::Json;
 use prettytable::Table;
+use prettytable::cell::Cell;
+use prettytable::row::Row;
 use prettytable::format;
+use regex::Regex;
 
-use benchmark::{Benchmarks, Benchmark};
+use benchmark::{Benchmarks, PairedBenchmarks, Benchmark, Column, Comparison, Direction,
+                RatioBase, Reduce, Record, RowFormat};
 use error::{Result, Error};
 
 mod benchmark;
+mod config;
 mod error;
+mod terminal;
 
 macro_rules! eprintln {
     ($($tt:tt)*) => {{
@@ -31,46 +48,390 @@ macro_rules! eprintln {
     }}
 }
 
+/// The narrowest that names are shortened to, to fit the table in the terminal.
+const MIN_NAME_WIDTH: usize = 12;
+
+/// How often --watch checks whether the input files changed.
+const WATCH_INTERVAL_MS: u64 = 500;
+
+/// The directory baselines are saved in by the save command.
+const BASELINE_DIR: &'static str = ".benchcmp";
+
+lazy_static! {
+    /// Compiler generated parts of benchmark names that --demangle removes:
+    /// closures, old and new style, and symbol hashes.
+    static ref MANGLING_REGEX: Regex =
+        Regex::new(r"::\{\{closure\}\}|::\{closure#[0-9]+\}|::h[0-9a-f]{16}$").unwrap();
+}
+
+/// The name used for stdin in error messages.
+const STDIN_NAME: &'static str = "<stdin>";
+
 const USAGE: &'static str = r#"
 Compares Rust micro-benchmark results.
 
 Usage:
+    cargo benchcmp save [options] <name> [<file>]
+    cargo benchcmp compare [options] <name> [<file>]
+    cargo benchcmp merge [options] <out> <inputs>...
+    cargo benchcmp trend [options] <old> <new>
     cargo benchcmp [options] <old> <new>
     cargo benchcmp [options] <old> <new> <file>
     cargo benchcmp -h | --help
     cargo benchcmp --version
 
-The first version takes two files and compares the common benchmarks.
+The <old> <new> version takes two files and compares the common benchmarks.
+Either file can also be a directory, in which case the benchmarks of all .txt
+and .bench files in it and its subdirectories are combined.
+
+The <old> <new> <file> version takes two benchmark name prefixes and one
+benchmark output file, and compares the common benchmarks (as determined by
+comparing the benchmark names with their prefixes stripped). Benchmarks not
+matching either prefix are ignored completely.
 
-The second version takes two benchmark name prefixes and one benchmark output
-file, and compares the common benchmarks (as determined by comparing the
-benchmark names with their prefixes stripped). Benchmarks not matching either
-prefix are ignored completely.
+If benchmark output is sent on stdin, then the <old> <new> <file> version is
+used and the third file parameter is not needed.
 
-If benchmark output is sent on stdin, then the second version is used and the
-third file parameter is not needed.
+The save command stores the benchmarks in <file>, or on stdin, in the
+.benchcmp directory under <name>. The compare command compares such a saved
+baseline with the benchmarks in <file>, or on stdin.
+
+The merge command combines the benchmarks in the <inputs> files, e.g. shards
+of one benchmark run, and writes them to <out> as cargo bench output. The runs
+of a benchmark that is in more than one input are averaged, or combined with
+the --reduce function.
+
+The trend command compares two --format json outputs of benchcmp, e.g. of two
+CI runs, to show how the change of every benchmark changed between them: its
+change % and direction in either output, and how many percentage points the
+change % moved. With --format json, every benchmark is an object with its
+name, an old and a new object as in the inputs (null if it is only in the
+other one), and their change_pct_diff, with the percentages rounded to
+hundredths.
+
+The exit status is 1 if a check like --fail-on-regression or --require fails,
+2 if an option or config file is invalid, 3 if an input can't be read and 4
+if the output, e.g. a file of --emit or the clipboard, can't be written.
 
 Options:
     -h, --help           Show this help message and exit.
     --version            Show the version.
+    --config <path>      Read default flags from this JSON file instead of
+                         .benchcmp.json. Flags on the command line override
+                         the defaults.
+    --input-format <fmt>
+                         Format of the inputs: text, the output of cargo bench,
+                         or json-array, a JSON object with a benchmarks array
+                         of objects with a name, ns and optionally a variance,
+                         throughput, iterations and percentiles.
+                         [default: text]
+    --metric <p>         Compare the ns/iter at a percentile, like p95, of
+                         json-array inputs with percentiles. Benchmarks that
+                         don't report it are left out.
+    --avg                <old>, <new> and <file> are comma separated lists of
+                         files with runs of the same benchmarks. The runs of
+                         each benchmark are averaged before comparing them.
+    --history <k>        <old> is a directory of earlier runs, one benchmark file
+                         per run, and the mean of the last <k> runs of each
+                         benchmark is compared with <new>. The runs are
+                         ordered by their paths, so name them by date.
+    --reduce <fn>        Combine the runs of a benchmark that appears more than
+                         once in an input with min, max, median or mean.
+                         The min and max keep the fastest or slowest run.
+                         With --avg, this replaces averaging.
+    --baseline <name>    Use the benchmarks of <old> or <new> that match this
+                         name as the old benchmarks, regardless of the order
+                         they were given in. With best, the old benchmarks
+                         are the fastest of the old and new benchmark of each
+                         name, to show how far new is from the best observed.
+                         Use --history and --reduce min to include every run.
     --threshold <n>      Show only comparisons with a percentage change greater
                          than this threshold.
+    --auto-threshold     Estimate the noise of the benchmarks, and show only
+                         comparisons with a percentage change greater than
+                         twice the median variance relative to ns/iter.
+    --threshold-regression <n>
+                         Like --threshold, but only hide regressions.
+    --threshold-improvement <n>
+                         Like --threshold, but only hide improvements.
+    --threshold-sigmas <k>
+                         Show only comparisons whose absolute difference is
+                         more than k times the average standard deviation of
+                         the old and new benchmark. Note that the variance
+                         libtest reports as (+/- X) is the range of its
+                         samples, so the standard deviation is estimated as a
+                         quarter of it.
+    --variance-is-stddev
+                         Take the variance of each benchmark to be its
+                         standard deviation, for output of tools other than
+                         libtest.
+    --ratio-base <base>  What the percentage change is relative to: old, new or
+                         geomean, the geometric mean of old and new. Going
+                         from 100 to 125 ns/iter is +25%, +20% and +22.36%.
+                         This affects the diff % column and the thresholds.
+                         [default: old]
+    --symmetric-pct      Compute the percentage change relative to the smaller
+                         of the old and new ns/iter instead of to the old one,
+                         so that swapping old and new only flips its sign.
+                         This affects the diff % column and the thresholds.
+    --min-diff-ns <n>    Show only comparisons with an absolute difference of
+                         at least this many nanoseconds.
+    --min-samples <n>    Warn about benchmarks that report an iteration count
+                         below n, as their measurements are unreliable.
+    --bounds             Show the lower and upper bound of each benchmark, that
+                         is its ns/iter minus and plus its variance.
+    --alarm <n>          Show comparisons with a percentage change of at least
+                         this much in bold, bright colors.
     --variance           Show the variance of each benchmark.
+    --variance-diff      Show the new variance as a factor of the old variance,
+                         to spot benchmarks that became noisier or calmer.
+    --rank               Show how many places each benchmark moved down when
+                         ranking the benchmarks from fastest to slowest, where
+                         equal ns/iter share a place.
+    --spark              Show a bar for the old and new ns/iter of each
+                         benchmark, scaled to the slowest of the two.
+    --ascii              Draw the --spark bars with ASCII characters.
+    --iterations         Show the number of iterations of each benchmark, for
+                         benchmark output that reports it.
+    --demangle           Remove compiler generated parts from benchmark names,
+                         such as ::{{closure}} and symbol hashes.
+    --by-test-leaf       Compare benchmarks by the last segment of their names
+                         only, ignoring the module they are in.
+    --separator-module <str>
+                         The separator between the modules and the test leaf
+                         in benchmark names, for --by-test-leaf.
+                         [default: ::]
+    --normalize-names    Pair up benchmarks whose names only differ in case or
+                         surrounding whitespace.
+    --match-pattern <re>
+                         Pair up benchmarks on the capture groups of this
+                         regex in their names, e.g. (\w+)_v[0-9]+::(\w+) pairs
+                         foo_v1::bench with foo_v2::bench. Benchmarks whose
+                         name doesn't match are paired on their whole name.
+    --rename-map <path>  Rename old benchmarks before pairing them, using a file
+                         with an old_name=new_name pair on every line. Empty
+                         lines and lines starting with # are skipped.
+    --exclude <regex>    Leave out the benchmarks whose name matches this regex.
+                         Use alternation to exclude several patterns, e.g.
+                         "slow|integration".
+    --label-fst <name>   Use this name in the header of the old column instead
+                         of the file name or prefix.
+    --label-snd <name>   Use this name in the header of the new column instead
+                         of the file name or prefix.
+    --columns <list>     Comma separated list of the columns to show, in order:
+                         name, fst, snd, diff_ns, diff_pct, diff, variance,
+                         throughput, speedup, variance_diff, rank and spark.
+                         Selecting variance or throughput shows them in their
+                         own column instead of after each measurement.
+    --separator <sep>    Thousands separator of the numbers in the table. JSON
+                         and CSV output never use one. [default: ,]
+    --compact            Leave the ns/iter unit out of the header, and only show
+                         the file name of the paths in it.
+    --combined-diff      Show the diff ns/iter followed by the diff % in one
+                         column, instead of in two.
+    --arrows             Prefix the diff % with an arrow up (▲) for an increase
+                         in ns/iter, or down (▼) for a decrease.
+    --relative           Show the old and new measurements as ratios to the old
+                         one, so the old column is always 1.00x.
+    --full-names         Don't shorten benchmark names to fit the table in the
+                         width of the terminal.
+    --plain              Print the table as plain space-padded columns, without
+                         any styles, for processing with other tools.
+    --precision <n>      Number of decimal places in the diff % column.
+                         [default: 2]
+    --round <digits>     Round the ns/iter and diff ns/iter in the table to this
+                         many significant digits. JSON and CSV output keep
+                         them as they are.
+    --preserve-order     Show the comparisons in the order the benchmarks appear
+                         in <old>, instead of sorted by name.
+    --sort <key>         Order of the comparisons: name, diff (the largest
+                         increase in ns/iter first) or throughput (the largest
+                         increase in throughput first). Sorting by diff or
+                         throughput overrides --preserve-order. [default: name]
+    --sections           Split the table into regressions, improvements and
+                         unchanged benchmarks, each under a header row, with
+                         the largest changes first. This overrides --sort.
+    --throughput-only    Show only benchmarks that report a throughput.
     --improvements       Show only improvements.
-    --regressions        Show only regressions.
+    --regressions        Show only regressions. Unchanged benchmarks are
+                         neither, so both leave them out.
+    --explain            Print why each benchmark left out of the table was
+                         left out, to stderr.
+    --worst              Print only the name and diff % of the largest
+                         regression shown in the table, instead of the table.
+                         Exit with status 1 if there is any regression, or
+                         with --fail-on-regression, one that reaches it.
+    --bigger-is-better   Treat an increase in ns/iter as an improvement rather
+                         than a regression. This flips the colors and also
+                         which rows --improvements and --regressions select.
+    --by-throughput      Compare the throughput in MB/s instead of the ns/iter,
+                         where bigger is better. Benchmarks without a
+                         throughput are left out, and lines that only report
+                         one, like "bench: 2,314 MB/s (+/- 12)", are read
+                         too. The variance of a line with ns/iter is in
+                         ns/iter, so it is taken to be 0.
+    --auto-metric        Compare the benchmarks that report a throughput in
+                         both old and new on their throughput in MB/s, where
+                         bigger is better, and the others on their ns/iter.
+    --fail-on-regression <n>
+                         Exit with status 1 if a comparison shown in the table
+                         is a regression of at least this percentage.
+    --fail-on-variance-increase <n>
+                         Exit with status 1 if the variance of a comparison
+                         shown in the table grew by at least this percentage.
+                         Like --fail-on-regression, benchmarks left out of
+                         the table by --threshold and the like don't count.
+    --budget <list>      Comma separated list of <name>=<ns> budgets. Every
+                         benchmark in new that takes more ns/iter than its
+                         budget is printed to stderr, with how far over budget
+                         it is, whether or not it is in old.
+    --fail-on-budget     Exit with status 1 if a benchmark is over its budget.
+    --require <names>    Comma separated list of benchmarks that must be in
+                         both old and new. Exit with status 1 if any is not.
+    --github-annotations
+                         After the table, print a GitHub Actions error for
+                         every regression and a notice for every improvement.
+                         With a failing percentage, only the regressions that
+                         reach it are errors.
+    --include-unchanged  After the table, print the number of benchmarks left
+                         out of it for being within a threshold to stderr.
+    --ci-summary         After the table, print a one line summary of the
+                         comparisons shown in it to stderr: the number of
+                         regressions, improvements and unchanged benchmarks,
+                         and the geometric mean of the new/old ratios.
+    --summary-weighted   Weigh the ratios in the geometric mean of the summary
+                         by their inverse variance, so that noisy benchmarks
+                         count less.
+    --coverage           Print to stderr how many benchmarks old and new have,
+                         and how many of them are in both.
+    --totals             End the table with a row that sums the benchmarks
+                         shown in it. If every benchmark reports its
+                         iteration count, the sum is an estimate of the total
+                         time in ns. Otherwise the ns/iter are summed.
+    --list               List the benchmarks of both inputs, and which of them
+                         are compared or only in one input, instead of
+                         comparing them.
+    --clipboard          Copy the table to the system clipboard instead of
+                         printing it.
+    --watch              Compare again whenever one of the input files changes,
+                         clearing the screen first. Stop with Ctrl-C.
+    --progress           Show how many of the files in a directory or history
+                         have been read so far on stderr, if it is a terminal.
+    -q, --quiet          Don't warn about benchmarks that are missing in old
+                         or new, or about renamed benchmarks. Errors are
+                         still shown.
+    --format <fmt>       Output format: table, json, csv, unified or influx. In
+                         json and csv, every comparison has a change_pct, the
+                         signed change in ns/iter from old to new, and a
+                         direction: improvement, regression or neutral. The
+                         unified format shows every comparison like a hunk of
+                         a unified diff. The influx format has an InfluxDB
+                         line protocol record for the old and the new
+                         benchmark of every comparison. [default: table]
+    --tag <list>         Comma separated list of key=value tags to add to every
+                         influx record, e.g. commit=4f2a1c9.
+    --emit <fmt:path> ...
+                         Also write the comparisons to a file, given as
+                         <format>:<path>, e.g. csv:report.csv. Can be given
+                         more than once.
     --color <when>       Show colored rows: never, always or auto [default: auto]
+    --palette <name>     Colors of the rows: default (red and green) or
+                         colorblind (yellow and blue). [default: default]
+    --theme <theme>      Background of the terminal: dark, light or auto. On a
+                         light background, the rows are colored without bright
+                         colors or yellow, which are hard to read on it. Auto
+                         reads the background from $COLORFGBG. [default: dark]
 "#;
 
 #[derive(Debug, RustcDecodable)]
 struct Args {
+    cmd_save: bool,
+    cmd_compare: bool,
+    cmd_merge: bool,
+    cmd_trend: bool,
     arg_old: String,
     arg_new: String,
+    arg_name: String,
     arg_file: Option<String>,
+    arg_out: String,
+    arg_inputs: Vec<String>,
+    flag_config: Option<String>,
+    flag_input_format: InputFormat,
+    flag_avg: bool,
+    flag_history: Option<usize>,
+    flag_reduce: Option<Reduce>,
+    flag_metric: Option<String>,
+    flag_baseline: Option<String>,
     flag_threshold: Option<u8>,
+    flag_auto_threshold: bool,
+    flag_threshold_regression: Option<u8>,
+    flag_threshold_improvement: Option<u8>,
+    flag_threshold_sigmas: Option<f64>,
+    flag_ratio_base: RatioBase,
+    flag_symmetric_pct: bool,
+    flag_variance_is_stddev: bool,
+    flag_min_diff_ns: Option<u64>,
+    flag_min_samples: Option<usize>,
+    flag_bounds: bool,
+    flag_alarm: Option<u8>,
     flag_variance: bool,
+    flag_variance_diff: bool,
+    flag_rank: bool,
+    flag_spark: bool,
+    flag_ascii: bool,
+    flag_iterations: bool,
+    flag_precision: usize,
+    flag_round: Option<usize>,
+    flag_separator: String,
+    flag_compact: bool,
+    flag_plain: bool,
+    flag_full_names: bool,
+    flag_relative: bool,
+    flag_arrows: bool,
+    flag_combined_diff: bool,
+    flag_columns: Option<String>,
+    flag_demangle: bool,
+    flag_by_test_leaf: bool,
+    flag_separator_module: String,
+    flag_normalize_names: bool,
+    flag_match_pattern: Option<String>,
+    flag_rename_map: Option<String>,
+    flag_exclude: Option<String>,
+    flag_label_fst: Option<String>,
+    flag_label_snd: Option<String>,
+    flag_preserve_order: bool,
+    flag_sort: Sort,
+    flag_sections: bool,
+    flag_throughput_only: bool,
     flag_improvements: bool,
     flag_regressions: bool,
+    flag_explain: bool,
+    flag_worst: bool,
+    flag_bigger_is_better: bool,
+    flag_by_throughput: bool,
+    flag_auto_metric: bool,
+    flag_fail_on_regression: Option<u8>,
+    flag_fail_on_variance_increase: Option<u32>,
+    flag_budget: Option<String>,
+    flag_fail_on_budget: bool,
+    flag_require: Option<String>,
+    flag_github_annotations: bool,
+    flag_ci_summary: bool,
+    flag_include_unchanged: bool,
+    flag_totals: bool,
+    flag_summary_weighted: bool,
+    flag_coverage: bool,
+    flag_quiet: bool,
+    flag_progress: bool,
+    flag_clipboard: bool,
+    flag_watch: bool,
+    flag_list: bool,
+    flag_format: Format,
+    flag_emit: Vec<String>,
+    flag_tag: Option<String>,
     flag_color: When,
+    flag_palette: Palette,
+    flag_theme: Theme,
 }
 
 #[derive(Debug, RustcDecodable)]
@@ -80,45 +441,580 @@ enum When {
     Auto,
 }
 
+#[derive(Debug, PartialEq)]
+enum Format {
+    Table,
+    Json,
+    Csv,
+    Unified,
+    Influx,
+}
+
+impl Format {
+    /// The names of all formats, in the form --format accepts them.
+    fn names() -> &'static [&'static str] {
+        &["table", "json", "csv", "unified", "influx"]
+    }
+}
+
+impl FromStr for Format {
+    type Err = ();
+
+    fn from_str(name: &str) -> result::Result<Format, ()> {
+        match &*name.to_lowercase() {
+            "table" => Ok(Format::Table),
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            "unified" => Ok(Format::Unified),
+            "influx" => Ok(Format::Influx),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Decodable for Format {
+    /// Decodes --format, listing the supported formats if the value is
+    /// none of them.
+    fn decode<D: Decoder>(d: &mut D) -> result::Result<Format, D::Error> {
+        let name = try!(d.read_str());
+        name.parse().map_err(|_| {
+            d.error(&format!("unknown format {}, expected one of: {}",
+                             name,
+                             Format::names().join(", ")))
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum InputFormat {
+    Text,
+    JsonArray,
+}
+
+impl Decodable for InputFormat {
+    fn decode<D: Decoder>(d: &mut D) -> result::Result<InputFormat, D::Error> {
+        let name = try!(d.read_str());
+        match &*name.to_lowercase() {
+            "text" => Ok(InputFormat::Text),
+            "json-array" => Ok(InputFormat::JsonArray),
+            _ => {
+                Err(d.error(&format!("unknown input format {}, expected one of: text, json-array",
+                                     name)))
+            }
+        }
+    }
+}
+
+#[derive(Debug, RustcDecodable)]
+enum Sort {
+    Name,
+    Diff,
+    Throughput,
+}
+
+#[derive(Debug, RustcDecodable)]
+enum Palette {
+    Default,
+    Colorblind,
+}
+
+#[derive(Debug, RustcDecodable)]
+enum Theme {
+    Dark,
+    Light,
+    Auto,
+}
+
 fn main() {
-    let args: Args = Docopt::new(USAGE)
-        .and_then(|d| d.version(Some(version())).decode())
-        .unwrap_or_else(|e| e.exit());
-    if let Err(e) = args.run() {
+    let args = parse_args(env::args().collect());
+    let result = if args.cmd_save {
+        args.save()
+    } else if args.cmd_merge {
+        args.merge()
+    } else if args.cmd_trend {
+        args.trend()
+    } else if args.flag_watch {
+        args.resolve_baseline().and_then(|args| args.watch())
+    } else {
+        args.resolve_baseline().and_then(|args| args.run())
+    };
+    if let Err(e) = result {
         eprintln!("{}", e);
-        process::exit(1);
+        process::exit(e.exit_code());
+    }
+}
+
+/// Parses the command line arguments, with the defaults from the config file
+/// for the flags that are not given.
+fn parse_args(argv: Vec<String>) -> Args {
+    let decode = |argv: Vec<String>| -> Args {
+        Docopt::new(USAGE)
+            .and_then(|d| d.argv(argv).version(Some(version())).decode())
+            .unwrap_or_else(|e| e.exit())
+    };
+    let args = decode(argv.clone());
+    match config::read_defaults(args.flag_config.as_ref().map(|p| &**p)) {
+        Ok(ref defaults) if defaults.is_empty() => args,
+        Ok(defaults) => decode(config::with_defaults(argv, defaults)),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(e.exit_code());
+        }
     }
 }
 
 impl Args {
-    fn run(&self) -> Result<()> {
+    /// Makes sure that the benchmarks named by --baseline are treated as the
+    /// old benchmarks, whichever order <old> and <new> were given in.
+    fn resolve_baseline(mut self) -> Result<Args> {
+        match self.flag_baseline.clone() {
+            None => {}
+            Some(ref baseline) if *baseline == self.arg_old => {}
+            Some(ref baseline) if *baseline == self.arg_new => {
+                mem::swap(&mut self.arg_old, &mut self.arg_new);
+            }
+            Some(ref baseline) if baseline == "best" => {}
+            Some(baseline) => return Err(Error::UnknownBaseline(baseline)),
+        }
+        Ok(self)
+    }
+
+    /// Returns whether the old benchmarks are the best of old and new, for
+    /// --baseline best. <old> or <new> named best takes precedence.
+    fn best_baseline(&self) -> bool {
+        self.flag_baseline.as_ref().map_or(false, |b| {
+            b == "best" && *b != self.arg_old && *b != self.arg_new
+        })
+    }
+
+    /// Saves the benchmarks in <file>, or on stdin, as the baseline <name>.
+    fn save(&self) -> Result<()> {
+        let mut no_variance = vec![];
+        let benches = try!(self.parse_input(&mut no_variance));
+        if !self.flag_quiet {
+            Args::note_no_variance(&no_variance);
+        }
+        try!(fs::create_dir_all(BASELINE_DIR).map_err(Error::Output));
+        let mut file = try!(File::create(Args::baseline_path(&self.arg_name))
+            .map_err(Error::Output));
+        try!(file.write_all(try!(json::encode(&benches)).as_bytes()).map_err(Error::Output));
+        Ok(())
+    }
+
+    /// Merges the benchmarks in <inputs> into <out>, combining the runs of
+    /// each benchmark.
+    fn merge(&self) -> Result<()> {
+        let mut benches = vec![];
+        let mut no_variance = vec![];
+        for input in &self.arg_inputs {
+            benches.extend(try!(self.parse_file(input, &mut no_variance)));
+        }
+        if !self.flag_quiet {
+            Args::note_no_variance(&no_variance);
+        }
+        let benches = benchmark::reduce(benches, self.flag_reduce.unwrap_or(Reduce::Mean));
+        let mut file = try!(File::create(&self.arg_out).map_err(Error::Output));
+        try!(writeln!(file, "running {} tests", benches.len()).map_err(Error::Output));
+        for bench in &benches {
+            try!(writeln!(file, "{}", bench).map_err(Error::Output));
+        }
+        Ok(())
+    }
+
+    /// Prints how the comparisons in two JSON outputs of benchcmp changed
+    /// between them, as a table or as JSON.
+    fn trend(&self) -> Result<()> {
+        let old = try!(Args::parse_records(&self.arg_old));
+        let new = try!(Args::parse_records(&self.arg_new));
+        let trends = benchmark::trends(old, new);
+        if self.flag_format == Format::Json {
+            println!("{}", try!(json::encode(&trends)));
+            return Ok(());
+        }
         let (name_old, name_new) = Args::names(&self.arg_old, &self.arg_new);
-        let benches = try!(self.parse_benchmarks()).paired();
-        if benches.comparisons().len() > 0 {
+        let name_old = self.flag_label_fst.clone().unwrap_or(name_old);
+        let name_new = self.flag_label_snd.clone().unwrap_or(name_new);
+        let change = |record: &Option<Record>| {
+            record.as_ref().map_or("-".to_string(), |r| {
+                format!("{:.*}% {}", self.flag_precision, r.change_pct, r.direction)
+            })
+        };
+        let mut output = Table::new();
+        output.set_format(*format::consts::FORMAT_CLEAN);
+        output.add_row(Row::new(vec![Cell::new("name"),
+                                     Cell::new(&format!("{} change %", name_old)),
+                                     Cell::new(&format!("{} change %", name_new)),
+                                     Cell::new("diff points")]));
+        for t in &trends {
+            let diff = t.change_pct_diff
+                .map_or("-".to_string(), |d| format!("{:+.*}", self.flag_precision, d));
+            output.add_row(Row::new(vec![Cell::new(&t.name),
+                                         Cell::new(&change(&t.old)).style_spec("r"),
+                                         Cell::new(&change(&t.new)).style_spec("r"),
+                                         Cell::new(&diff).style_spec("r")]));
+        }
+        if self.flag_plain {
+            print!("{}", plain(&output));
+        } else {
+            try!(output.print(&mut io::stdout()).map_err(Error::Output));
+        }
+        Ok(())
+    }
+
+    /// Parses the comparisons in a --format json output of benchcmp.
+    fn parse_records(path: &str) -> Result<Vec<Record>> {
+        let mut records = String::new();
+        try!(try!(open_file(path)).read_to_string(&mut records));
+        json::decode(&records).map_err(|e| {
+            Error::JsonReport {
+                source: path.to_string(),
+                msg: e.to_string(),
+            }
+        })
+    }
+
+    /// Runs the comparison every time one of the input files changes,
+    /// clearing the screen in between, until the process is interrupted.
+    fn watch(&self) -> Result<()> {
+        let mut last = None;
+        loop {
+            let stamps = try!(self.input_stamps());
+            if last.as_ref() != Some(&stamps) {
+                print!("\x1b[2J\x1b[H");
+                // Keep watching, the inputs may be written to right now.
+                if let Err(e) = self.run() {
+                    eprintln!("{}", e);
+                }
+                last = Some(stamps);
+            }
+            thread::sleep(Duration::from_millis(WATCH_INTERVAL_MS));
+        }
+    }
+
+    /// Returns the input files with the time they were last modified, to
+    /// find out whether any of them changed.
+    fn input_stamps(&self) -> Result<Vec<(PathBuf, Option<SystemTime>)>> {
+        let inputs = if self.cmd_compare {
+            vec![self.arg_file.clone().unwrap_or_default()]
+        } else if let Some(ref one_file) = self.arg_file {
+            vec![one_file.clone()]
+        } else {
+            vec![self.arg_old.clone(), self.arg_new.clone()]
+        };
+        let mut paths = vec![];
+        if self.cmd_compare {
+            paths.push(Args::baseline_path(&self.arg_name));
+        }
+        for input in &inputs {
+            let files: Vec<&str> = if self.flag_avg {
+                input.split(',').collect()
+            } else {
+                vec![input]
+            };
+            for file in files {
+                if file.is_empty() || file == "-" {
+                    return Err(Error::WatchStdin);
+                } else if Path::new(file).is_dir() {
+                    try!(Args::bench_files(Path::new(file), &mut paths));
+                } else {
+                    paths.push(PathBuf::from(file));
+                }
+            }
+        }
+        paths.sort();
+        Ok(paths.into_iter()
+            .map(|path| {
+                let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+                (path, modified)
+            })
+            .collect())
+    }
+
+    fn run(&self) -> Result<()> {
+        let emits = try!(self.emits());
+        let budgets = try!(self.budgets());
+        let (name_old, name_new) = if self.cmd_compare {
+            Args::names(&self.arg_name, self.arg_file.as_ref().map_or("", |f| f))
+        } else {
+            Args::names(&self.arg_old, &self.arg_new)
+        };
+        let name_old = if self.best_baseline() {
+            "best".to_string()
+        } else {
+            name_old
+        };
+        let name_old = self.flag_label_fst.clone().unwrap_or(name_old);
+        let name_new = self.flag_label_snd.clone().unwrap_or(name_new);
+        let (benches, no_variance) = try!(self.parse_benchmarks());
+        let pattern = match self.flag_match_pattern {
+            Some(ref pattern) => Some(try!(Regex::new(pattern))),
+            None => None,
+        };
+        let benches = match (pattern, self.flag_normalize_names) {
+            (Some(pattern), normalize) => {
+                benches.paired_by(|name| {
+                    let key = Args::match_key(name, &pattern);
+                    if normalize { Args::normalize_name(&key) } else { key }
+                })
+            }
+            (None, true) => benches.paired_by(Args::normalize_name),
+            (None, false) => benches.paired(),
+        };
+        let benches = if self.flag_auto_metric {
+            benches.auto_metric()
+        } else {
+            benches
+        };
+        let benches = if self.flag_preserve_order {
+            benches.in_input_order()
+        } else {
+            benches
+        };
+        let benches = if self.flag_ratio_base != RatioBase::Old {
+            benches.with_ratio_base(self.flag_ratio_base)
+        } else {
+            benches
+        };
+        let benches = if self.flag_symmetric_pct {
+            benches.symmetric()
+        } else {
+            benches
+        };
+        if self.flag_list {
+            return Args::list(&benches, &name_old, &name_new).map_err(Error::in_output);
+        }
+        if self.flag_worst {
+            return self.worst(&benches);
+        }
+        let merged = benches.comparisons()
+            .iter()
+            .filter(|c| c.old.name != c.new.name)
+            .map(|c| format!("{} = {}", c.old.name, c.new.name))
+            .collect::<Vec<String>>();
+        if !merged.is_empty() && !self.flag_quiet {
+            eprintln!("NOTE: paired benchmarks with different names: {}",
+                      merged.join(", "));
+        }
+        let mut shown = vec![];
+        let mut unchanged = 0;
+        // An empty table is not printed, but still written to the files of --emit.
+        let print = benches.comparisons().len() > 0 || self.flag_format != Format::Table;
+        if print || !emits.is_empty() {
+            // The styles of the rows after the header, for --full-names.
+            let mut styles = vec![];
             let mut output = Table::new();
             output.set_format(*format::consts::FORMAT_CLEAN);
-            output.add_row(row![
-                b->"name",
-                b->format!("{} ns/iter", name_old),
-                b->format!("{} ns/iter", name_new),
-                br->"diff ns/iter",
-                br->"diff %"
-            ]);
-            for c in benches.comparisons() {
-                let abs_per = (c.diff_ratio * 100f64).abs().trunc() as u8;
-                let regression = c.diff_ns < 0;
-                if self.flag_threshold.map_or(false, |t| abs_per < t) ||
-                   self.flag_regressions && regression ||
-                   self.flag_improvements && !regression {
+            let row_format = RowFormat {
+                columns: try!(self.columns()),
+                variance: self.flag_variance,
+                bounds: self.flag_bounds,
+                iterations: self.flag_iterations,
+                precision: self.flag_precision,
+                round: self.flag_round,
+                separator: self.flag_separator.clone(),
+                relative: self.flag_relative,
+                arrows: self.flag_arrows,
+                ascii: self.flag_ascii,
+            };
+            output.add_row(self.header(&row_format, &name_old, &name_new));
+            let auto_threshold = if self.flag_auto_threshold {
+                self.auto_threshold(benches.comparisons())
+            } else {
+                None
+            };
+            let mut section = None;
+            for c in self.sorted(benches.comparisons()) {
+                if let Some(reason) = self.skip_reason(c, auto_threshold) {
+                    if self.below_threshold(c, auto_threshold).is_some() {
+                        unchanged += 1;
+                    }
+                    if self.flag_explain {
+                        eprintln!("skipped {}: {}", c.old.name, reason);
+                    }
                     continue;
                 }
-                output.add_row(c.to_row(self.flag_variance, regression));
+                let direction = c.direction(self.bigger_is_better());
+                if self.flag_sections && section != Some(direction) {
+                    let title = match direction {
+                        Direction::Regression => "regressions:",
+                        Direction::Improvement => "improvements:",
+                        Direction::Neutral => "unchanged:",
+                    };
+                    output.add_row(Row::new(vec![Cell::new(title).style_spec("b")]));
+                    styles.push(String::new());
+                    section = Some(direction);
+                }
+                let regression = self.is_regression(c);
+                let style = self.row_style(c, regression);
+                output.add_row(c.to_row(&row_format, &style));
+                styles.push(style);
+                shown.push(c);
+            }
+            if self.flag_explain {
+                for b in benches.missing_old() {
+                    eprintln!("skipped {}: not in new", b.name);
+                }
+                for b in benches.missing_new() {
+                    eprintln!("skipped {}: not in old", b.name);
+                }
+            }
+            if self.flag_totals && !shown.is_empty() {
+                output.add_row(benchmark::totals(&shown).to_row(&row_format, ""));
+                styles.push(String::new());
             }
 
-            match self.flag_color {
-                When::Auto => output.printstd(),
-                When::Never => try!(output.print(&mut io::stdout())),
-                When::Always => output.print_tty(true),
+            if let (false, Some(width)) = (self.flag_full_names, terminal::width()) {
+                if self.flag_format == Format::Table && !self.flag_plain && !self.flag_clipboard {
+                    Args::fit_names(&mut output, &row_format, &styles, width);
+                }
+            }
+
+            for (format, path) in emits {
+                let mut file = try!(File::create(&path).map_err(Error::Output));
+                try!(self.write_output(&mut file, &format, &output, &row_format, &shown)
+                    .map_err(Error::in_output));
+            }
+            if print {
+                if self.flag_clipboard {
+                    let mut rendered = vec![];
+                    try!(self.write_output(&mut rendered,
+                                           &self.flag_format,
+                                           &output,
+                                           &row_format,
+                                           &shown));
+                    try!(copy_to_clipboard(&rendered).map_err(Error::in_output));
+                } else if self.flag_format == Format::Table && !self.flag_plain {
+                    match self.flag_color {
+                        When::Auto => output.printstd(),
+                        When::Never => try!(output.print(&mut io::stdout()).map_err(Error::Output)),
+                        When::Always => output.print_tty(true),
+                    }
+                } else {
+                    try!(self.write_output(&mut io::stdout(),
+                                           &self.flag_format,
+                                           &output,
+                                           &row_format,
+                                           &shown)
+                        .map_err(Error::in_output));
+                }
+            }
+        }
+        if self.flag_github_annotations {
+            try!(self.github_annotations(&shown).map_err(Error::in_output));
+        }
+        if self.flag_ci_summary {
+            eprintln!("{}", self.ci_summary(&shown));
+        }
+        if self.flag_include_unchanged {
+            eprintln!("{} unchanged within threshold", unchanged);
+        }
+        if self.flag_coverage {
+            eprintln!("{}", Args::coverage(&benches, &name_old, &name_new));
+        }
+        let over_budget = self.over_budget(&benches, &budgets);
+        let failed = shown.iter().filter(|c| self.fails(c)).count();
+        let noisier = shown.iter().filter(|c| self.got_noisier(c)).count();
+
+        if !self.flag_quiet {
+            self.warn(&benches, &no_variance);
+        }
+        let mut failures = vec![];
+        if let Some(ref required) = self.flag_require {
+            let cmps = benches.comparisons();
+            let missing = required.split(',')
+                .filter(|name| !cmps.iter().any(|c| c.old.name == *name || c.new.name == *name))
+                .map(str::to_string)
+                .collect::<Vec<String>>();
+            if !missing.is_empty() {
+                failures.push(Error::MissingRequired(missing));
+            }
+        }
+        if let (Some(threshold), true) = (self.flag_fail_on_regression, failed > 0) {
+            failures.push(Error::Regressions {
+                count: failed,
+                threshold: threshold,
+            });
+        }
+        if let (Some(threshold), true) = (self.flag_fail_on_variance_increase, noisier > 0) {
+            failures.push(Error::NoisierVariance {
+                count: noisier,
+                threshold: threshold,
+            });
+        }
+        if self.flag_fail_on_budget && over_budget > 0 {
+            failures.push(Error::OverBudget(over_budget));
+        }
+        // Report every check that failed, not just the first.
+        match failures.len() {
+            0 => Ok(()),
+            1 => Err(failures.remove(0)),
+            _ => Err(Error::Failures(failures)),
+        }
+    }
+
+    /// Prints every benchmark in new that takes longer than its --budget to
+    /// stderr, with how far over budget it is in percent, and returns how
+    /// many there are. Whether the benchmark is in old doesn't matter.
+    fn over_budget(&self, benches: &PairedBenchmarks, budgets: &[(String, u64)]) -> usize {
+        let news = benches.comparisons().iter().map(|c| &c.new).chain(benches.missing_new());
+        let mut over = 0;
+        let mut missing = vec![];
+        for &(ref name, budget) in budgets {
+            match news.clone().find(|b| b.name == *name) {
+                Some(bench) if bench.ns > budget => {
+                    over += 1;
+                    eprintln!("OVER BUDGET: {} takes {} ns/iter, {:.*}% over its budget of {}",
+                              name,
+                              bench.ns,
+                              self.flag_precision,
+                              (bench.ns - budget) as f64 / budget as f64 * 100f64,
+                              budget);
+                }
+                Some(_) => {}
+                None => missing.push(name.clone()),
+            }
+        }
+        if !missing.is_empty() && !self.flag_quiet {
+            eprintln!("WARNING: benchmarks with a budget but not in new: {}",
+                      missing.join(", "));
+        }
+        over
+    }
+
+    /// Notes the benchmarks that were parsed without a valid variance.
+    fn note_no_variance(no_variance: &[String]) {
+        for name in no_variance {
+            eprintln!("NOTE: no valid variance for {}, using 0", name);
+        }
+    }
+
+    /// Warns about inputs that look the same and unpaired benchmarks.
+    fn warn(&self, benches: &PairedBenchmarks, no_variance: &[String]) {
+        // Comparing an input with itself is most likely a mistake.
+        if !self.cmd_compare && self.arg_old == self.arg_new {
+            eprintln!("WARNING: old and new are the same input: {}", self.arg_old);
+        } else if !benches.comparisons().is_empty() &&
+                  benches.comparisons()
+                      .iter()
+                      .all(|c| c.diff_ns == 0 && c.old.variance == c.new.variance) {
+            eprintln!("WARNING: no benchmark changed at all, old and new may be the same input");
+        }
+
+        Args::note_no_variance(no_variance);
+
+        // Too few iterations make for unreliable measurements.
+        if let Some(min) = self.flag_min_samples {
+            let few = benches.comparisons()
+                .iter()
+                .filter(|c| {
+                    [&c.old, &c.new].iter().any(|b| b.iterations.map_or(false, |n| n < min))
+                })
+                .map(|c| c.old.name.to_string())
+                .collect::<Vec<String>>();
+            if !few.is_empty() {
+                eprintln!("WARNING: benchmarks with fewer than {} iterations: {}",
+                          min,
+                          few.join(", "));
             }
         }
 
@@ -139,55 +1035,901 @@ impl Args {
                 .join(", ");
             eprintln!("WARNING: benchmarks in new but not in old: {}", missed);
         }
+    }
+
+    /// Returns whether a comparison is a regression that reaches the
+    /// --fail-on-regression percentage.
+    fn fails(&self, c: &Comparison) -> bool {
+        self.flag_fail_on_regression
+            .map_or(false, |t| self.is_regression(c) && (c.diff_ratio * 100f64).abs() >= t as f64)
+    }
+
+    /// Returns whether the variance of a comparison grew by the
+    /// --fail-on-variance-increase percentage. Any variance is an infinite
+    /// increase over a variance of zero.
+    fn got_noisier(&self, c: &Comparison) -> bool {
+        self.flag_fail_on_variance_increase.map_or(false, |t| {
+            c.variance_ratio().map_or(true, |ratio| (ratio - 1f64) * 100f64 >= t as f64)
+        })
+    }
+
+    /// Prints a GitHub Actions workflow command for every regression and
+    /// improvement, so that they show up as annotations. With
+    /// --fail-on-regression, only the regressions that fail are errors.
+    fn github_annotations(&self, shown: &[&Comparison]) -> Result<()> {
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        for c in shown {
+            let pct = (c.diff_ratio * 100f64).abs();
+            match c.direction(self.bigger_is_better()) {
+                Direction::Regression if self.flag_fail_on_regression.is_none() ||
+                                         self.fails(c) => {
+                    try!(writeln!(out,
+                                  "::error title=Perf regression::{} regressed {:.*}%",
+                                  c.old.name,
+                                  self.flag_precision,
+                                  pct));
+                }
+                Direction::Improvement => {
+                    try!(writeln!(out,
+                                  "::notice title=Perf improvement::{} improved {:.*}%",
+                                  c.old.name,
+                                  self.flag_precision,
+                                  pct));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Prints the regression with the largest diff % among the comparisons
+    /// that the filters leave in the table, instead of the table. Fails if
+    /// there is any regression, or any that reaches --fail-on-regression.
+    fn worst(&self, benches: &PairedBenchmarks) -> Result<()> {
+        let auto_threshold = if self.flag_auto_threshold {
+            self.auto_threshold(benches.comparisons())
+        } else {
+            None
+        };
+        let regressions = benches.comparisons()
+            .iter()
+            .filter(|c| self.skip_reason(c, auto_threshold).is_none() && self.is_regression(c))
+            .collect::<Vec<&Comparison>>();
+        let worst = regressions.iter()
+            .max_by(|a, b| {
+                a.diff_ratio.abs().partial_cmp(&b.diff_ratio.abs()).unwrap_or(Ordering::Equal)
+            });
+        if let Some(c) = worst {
+            println!("{} {:.*}%", c.old.name, self.flag_precision, c.diff_ratio * 100f64);
+        }
+        match self.flag_fail_on_regression {
+            Some(threshold) => {
+                let failed = regressions.iter().filter(|c| self.fails(c)).count();
+                if failed > 0 {
+                    return Err(Error::Regressions {
+                        count: failed,
+                        threshold: threshold,
+                    });
+                }
+            }
+            None if !regressions.is_empty() => {
+                return Err(Error::Regressed(regressions.len()));
+            }
+            None => {}
+        }
         Ok(())
     }
 
-    /// Parse benchmarks from the command line invocation given.
-    fn parse_benchmarks(&self) -> Result<Benchmarks> {
-        if let Some(ref one_file) = self.arg_file {
+    /// Prints the benchmarks of both inputs, and which of them would be
+    /// compared or dropped, instead of the comparison table.
+    fn list(benches: &PairedBenchmarks, name_old: &str, name_new: &str) -> Result<()> {
+        let names = |benches: &[Benchmark]| -> Vec<String> {
+            benches.iter().map(|b| b.name.clone()).collect()
+        };
+        let mut olds = names(benches.missing_old());
+        olds.extend(benches.comparisons().iter().map(|c| c.old.name.clone()));
+        olds.sort();
+        let mut news = names(benches.missing_new());
+        news.extend(benches.comparisons().iter().map(|c| c.new.name.clone()));
+        news.sort();
+        let both = benches.comparisons()
+            .iter()
+            .map(|c| if c.old.name == c.new.name {
+                c.old.name.clone()
+            } else {
+                format!("{} = {}", c.old.name, c.new.name)
+            })
+            .collect();
+        let sections = vec![(name_old.to_string(), olds),
+                            (name_new.to_string(), news),
+                            ("compared".to_string(), both),
+                            (format!("only in {}", name_old), names(benches.missing_old())),
+                            (format!("only in {}", name_new), names(benches.missing_new()))];
+
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        for (title, names) in sections {
+            try!(writeln!(out, "{} ({}):", title, names.len()));
+            for name in names {
+                try!(writeln!(out, "    {}", name));
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the comparisons shown in the table in the given format,
+    /// without colors.
+    fn write_output<W: Write>(&self,
+                              out: &mut W,
+                              format: &Format,
+                              table: &Table,
+                              fmt: &RowFormat,
+                              shown: &[&Comparison])
+                              -> Result<()> {
+        let records: Vec<_> = shown.iter()
+            .map(|c| c.to_record(self.bigger_is_better()))
+            .collect();
+        match *format {
+            Format::Table if self.flag_plain => try!(write!(out, "{}", plain(table))),
+            Format::Table => try!(table.print(out)),
+            Format::Json => try!(writeln!(out, "{}", try!(json::encode(&records)))),
+            Format::Csv => {
+                try!(writeln!(out, "{}", Record::csv_header()));
+                for record in records {
+                    try!(writeln!(out, "{}", record.to_csv()));
+                }
+            }
+            Format::Unified => {
+                for c in shown {
+                    try!(write!(out, "{}", c.to_unified(fmt)));
+                }
+            }
+            Format::Influx => {
+                let mut tags = try!(self.tags());
+                let since_epoch = try!(SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e)));
+                let timestamp = since_epoch.as_secs() * 1_000_000_000 +
+                                since_epoch.subsec_nanos() as u64;
+                for c in shown {
+                    for &(side, bench) in &[("old", &c.old), ("new", &c.new)] {
+                        tags.push(("side".to_string(), side.to_string()));
+                        try!(writeln!(out, "{}", bench.to_influx(&tags, timestamp)));
+                        tags.pop();
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the name=ns pairs given with --budget.
+    fn budgets(&self) -> Result<Vec<(String, u64)>> {
+        let budgets = match self.flag_budget {
+            Some(ref budgets) => budgets,
+            None => return Ok(vec![]),
+        };
+        budgets.split(',')
+            .map(|budget| {
+                let mut parts = budget.rsplitn(2, '=');
+                match (parts.next().map(str::parse), parts.next()) {
+                    (Some(Ok(ns)), Some(name)) if ns > 0 && !name.is_empty() => {
+                        Ok((name.to_string(), ns))
+                    }
+                    _ => Err(Error::InvalidBudget(budget.to_string())),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the key=value pairs given with --tag.
+    fn tags(&self) -> Result<Vec<(String, String)>> {
+        let tags = match self.flag_tag {
+            Some(ref tags) => tags,
+            None => return Ok(vec![]),
+        };
+        tags.split(',')
+            .map(|tag| {
+                let mut parts = tag.splitn(2, '=');
+                match (parts.next(), parts.next()) {
+                    (Some(key), Some(value)) if !key.is_empty() && !value.is_empty() => {
+                        Ok((key.to_string(), value.to_string()))
+                    }
+                    _ => Err(Error::InvalidTag(tag.to_string())),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the threshold in percent for --auto-threshold: twice the
+    /// median relative variance of all benchmarks.
+    fn auto_threshold(&self, cmps: &[Comparison]) -> Option<f64> {
+        let threshold = benchmark::noise_floor(cmps).map(|floor| 2f64 * floor * 100f64);
+        if let (Some(threshold), false) = (threshold, self.flag_quiet) {
+            eprintln!("NOTE: --auto-threshold is {:.*}% (twice the median relative variance)",
+                      self.flag_precision,
+                      threshold);
+        }
+        threshold
+    }
+
+    /// Returns the comparisons in the order given by --sort.
+    fn sorted<'a>(&self, cmps: &'a [Comparison]) -> Vec<&'a Comparison> {
+        let mut cmps: Vec<&Comparison> = cmps.iter().collect();
+        match self.flag_sort {
+            Sort::Name => {}
+            Sort::Diff => {
+                cmps.sort_by(|a, b| {
+                    b.diff_ratio.partial_cmp(&a.diff_ratio).unwrap_or(Ordering::Equal)
+                });
+            }
+            Sort::Throughput => {
+                // Benchmarks without a throughput go last.
+                let key = |c: &Comparison| c.throughput_change().unwrap_or(f64::NEG_INFINITY);
+                cmps.sort_by(|a, b| key(b).partial_cmp(&key(a)).unwrap_or(Ordering::Equal));
+            }
+        }
+        if self.flag_sections {
+            let section = |c: &Comparison| match c.direction(self.bigger_is_better()) {
+                Direction::Regression => 0,
+                Direction::Improvement => 1,
+                Direction::Neutral => 2,
+            };
+            let size = |c: &Comparison| c.diff_ratio.abs();
+            cmps.sort_by(|a, b| {
+                match section(a).cmp(&section(b)) {
+                    Ordering::Equal => size(b).partial_cmp(&size(a)).unwrap_or(Ordering::Equal),
+                    order => order,
+                }
+            });
+        }
+        cmps
+    }
+
+    /// Returns whether a comparison is a regression, as opposed to an
+    /// improvement or no change at all.
+    fn is_regression(&self, c: &Comparison) -> bool {
+        c.direction(self.bigger_is_better()) == Direction::Regression
+    }
+
+    /// Returns why the filters leave a comparison out of the table, or `None`
+    /// if it is shown.
+    fn skip_reason(&self, c: &Comparison, auto_threshold: Option<f64>) -> Option<String> {
+        let direction = c.direction(self.bigger_is_better());
+        if self.flag_throughput_only && c.throughput_change().is_none() {
+            return Some("no throughput".to_string());
+        }
+        if let Some(reason) = self.below_threshold(c, auto_threshold) {
+            return Some(reason);
+        }
+        // Unchanged benchmarks are neither, so they are left out by both.
+        if self.flag_regressions && direction != Direction::Regression {
+            return Some("not a regression".to_string());
+        }
+        if self.flag_improvements && direction != Direction::Improvement {
+            return Some("not an improvement".to_string());
+        }
+        None
+    }
+
+    /// Returns how a comparison falls below one of the thresholds, or `None`
+    /// if it doesn't.
+    fn below_threshold(&self, c: &Comparison, auto_threshold: Option<f64>) -> Option<String> {
+        let per = (c.diff_ratio * 100f64).abs();
+        let abs_per = per.trunc() as u8;
+        let regression = self.is_regression(c);
+        let improvement = c.direction(self.bigger_is_better()) == Direction::Improvement;
+        let below = |kind: &str, t: u8| format!("{}{:.2}% below threshold {}%", kind, per, t);
+        match self.flag_threshold {
+            Some(t) if abs_per < t => return Some(below("", t)),
+            _ => {}
+        }
+        match auto_threshold {
+            Some(t) if per < t => {
+                return Some(format!("{:.2}% below auto threshold {:.2}%", per, t));
+            }
+            _ => {}
+        }
+        match self.flag_threshold_regression {
+            Some(t) if regression && abs_per < t => return Some(below("regression of ", t)),
+            _ => {}
+        }
+        match self.flag_threshold_improvement {
+            Some(t) if improvement && abs_per < t => return Some(below("improvement of ", t)),
+            _ => {}
+        }
+        if let Some(k) = self.flag_threshold_sigmas {
+            if !c.exceeds_stddev(k, self.flag_variance_is_stddev) {
+                return Some(format!("difference of {} ns within {} standard deviations",
+                                    c.diff_ns.abs(),
+                                    k));
+            }
+        }
+        match self.flag_min_diff_ns {
+            Some(n) if (c.diff_ns.abs() as u64) < n => {
+                return Some(format!("difference of {} ns below {} ns", c.diff_ns.abs(), n));
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Returns whether an increase of the compared value is an improvement,
+    /// as with --bigger-is-better or when comparing throughput.
+    fn bigger_is_better(&self) -> bool {
+        self.flag_bigger_is_better || self.flag_by_throughput
+    }
+
+    /// Summarizes the comparisons shown in the table on a single line.
+    fn ci_summary(&self, shown: &[&Comparison]) -> String {
+        let unchanged = shown.iter().filter(|c| c.diff_ns == 0).count();
+        let regressions = shown.iter().filter(|c| self.is_regression(c)).count();
+        let improvements = shown.len() - unchanged - regressions;
+        let (label, geomean) = if self.flag_summary_weighted {
+            ("weighted geomean", benchmark::weighted_geomean(shown))
+        } else {
+            ("geomean", benchmark::geomean(shown))
+        };
+        let geomean = match geomean {
+            Some(ratio) => format!("{:+.*}%", self.flag_precision, (ratio - 1f64) * 100f64),
+            None => "n/a".to_string(),
+        };
+        format!("benchcmp: {} regressions, {} improvements, {} unchanged ({} {})",
+                regressions,
+                improvements,
+                unchanged,
+                label,
+                geomean)
+    }
+
+    /// Reports how many benchmarks each input has and how many of them were
+    /// paired up.
+    fn coverage(benches: &PairedBenchmarks, name_old: &str, name_new: &str) -> String {
+        let common = benches.comparisons().len();
+        format!("benchcmp: {} has {} benchmarks, {} has {}, {} in common",
+                name_old,
+                common + benches.missing_old().len(),
+                name_new,
+                common + benches.missing_new().len(),
+                common)
+    }
+
+    /// Returns the header row of the comparison table.
+    fn header(&self, fmt: &RowFormat, name_old: &str, name_new: &str) -> Row {
+        let suffix = match (self.flag_compact || self.flag_auto_metric, self.flag_by_throughput) {
+            (true, _) => "",
+            (false, true) => " MB/s",
+            (false, false) => " ns/iter",
+        };
+        let mut cells = vec![];
+        for column in &fmt.columns {
+            match *column {
+                Column::Name => cells.push(Cell::new("name").style_spec("b")),
+                Column::Fst | Column::Snd => {
+                    let name = if *column == Column::Fst { name_old } else { name_new };
+                    let name = if self.flag_compact {
+                        Path::new(name).file_name().and_then(|n| n.to_str()).unwrap_or(name)
+                    } else {
+                        name
+                    };
+                    let unit = if fmt.relative { "" } else { suffix };
+                    cells.push(Cell::new(&format!("{}{}", name, unit)).style_spec("b"));
+                    if fmt.bounds {
+                        cells.push(Cell::new(&format!("{} lower", name)).style_spec("b"));
+                        cells.push(Cell::new(&format!("{} upper", name)).style_spec("b"));
+                    }
+                }
+                Column::DiffNs => {
+                    cells.push(Cell::new(&format!("diff{}", suffix)).style_spec("br"))
+                }
+                Column::DiffPct => cells.push(Cell::new("diff %").style_spec("br")),
+                Column::Diff => cells.push(Cell::new(&format!("diff{}", suffix)).style_spec("br")),
+                Column::Variance => cells.push(Cell::new("variance").style_spec("br")),
+                Column::Throughput => cells.push(Cell::new("throughput").style_spec("br")),
+                Column::Speedup => cells.push(Cell::new("speedup").style_spec("br")),
+                Column::VarianceDiff => {
+                    cells.push(Cell::new("variance diff").style_spec("br"))
+                }
+                Column::Rank => cells.push(Cell::new("rank").style_spec("br")),
+                Column::Spark => cells.push(Cell::new("spark").style_spec("b")),
+            }
+        }
+        Row::new(cells)
+    }
+
+    /// Returns the columns selected with --columns, or the default columns.
+    /// --variance-diff and --rank add their columns if they aren't selected.
+    fn columns(&self) -> Result<Vec<Column>> {
+        let mut columns = match self.flag_columns {
+            None => Column::defaults(),
+            Some(ref columns) => {
+                try!(columns.split(',')
+                    .map(|name| {
+                        name.trim()
+                            .parse()
+                            .map_err(|_| Error::UnknownColumn(name.trim().to_string()))
+                    })
+                    .collect())
+            }
+        };
+        if self.flag_combined_diff {
+            let first = columns.iter().position(|&c| c == Column::DiffNs || c == Column::DiffPct);
+            if let Some(first) = first {
+                columns[first] = Column::Diff;
+            }
+            columns.retain(|&c| c != Column::DiffNs && c != Column::DiffPct);
+        }
+        if self.flag_variance_diff && !columns.contains(&Column::VarianceDiff) {
+            columns.push(Column::VarianceDiff);
+        }
+        if self.flag_rank && !columns.contains(&Column::Rank) {
+            columns.push(Column::Rank);
+        }
+        if self.flag_spark && !columns.contains(&Column::Spark) {
+            columns.push(Column::Spark);
+        }
+        Ok(columns)
+    }
+
+    /// Shortens the names in the table, ending them with an ellipsis, so that
+    /// its rows fit in `width` columns. Names are never shortened to less
+    /// than `MIN_NAME_WIDTH` characters.
+    fn fit_names(table: &mut Table, fmt: &RowFormat, styles: &[String], width: usize) {
+        if !fmt.columns.contains(&Column::Name) {
+            return;
+        }
+        // Fst and Snd take three cells with --bounds.
+        let name_cell: usize = fmt.columns
+            .iter()
+            .take_while(|&&c| c != Column::Name)
+            .map(|&c| if fmt.bounds && (c == Column::Fst || c == Column::Snd) { 3 } else { 1 })
+            .sum();
+        // Every cell of the clean format is padded with a space on each side.
+        let used: usize = (0..table.get_column_num())
+            .map(|i| table.column_iter(i).map(|cell| cell.get_width()).max().unwrap_or(0) + 2)
+            .sum();
+        let name_width = table.column_iter(name_cell).map(|c| c.get_width()).max().unwrap_or(0);
+        if used <= width {
+            return;
+        }
+        let room = cmp::max(MIN_NAME_WIDTH, name_width.saturating_sub(used - width));
+        for (row, style) in table.row_iter_mut().skip(1).zip(styles) {
+            let name = match row.get_cell(name_cell) {
+                Some(cell) if cell.get_width() > room => cell.get_content(),
+                _ => continue,
+            };
+            let mut short: String = name.chars().take(room - 1).collect();
+            short.push('…');
+            let _ = row.set_cell(Cell::new(&short).style_spec(style), name_cell);
+        }
+    }
+
+    /// Returns the formats and paths of the files to write with --emit.
+    fn emits(&self) -> Result<Vec<(Format, String)>> {
+        self.flag_emit
+            .iter()
+            .map(|emit| {
+                let mut parts = emit.splitn(2, ':');
+                match (parts.next().map(str::parse), parts.next()) {
+                    (Some(Ok(format)), Some(path)) if !path.is_empty() => {
+                        Ok((format, path.to_string()))
+                    }
+                    _ => Err(Error::InvalidEmit(emit.to_string())),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the style used to color the row of a comparison.
+    ///
+    /// Regressions are red and improvements are green (yellow and blue with
+    /// the colorblind palette), in bold, bright colors when the change
+    /// reaches the alarm percentage. On a light background, yellow is
+    /// replaced by magenta and alarms are only bold. Unchanged benchmarks are
+    /// not colored at all.
+    fn row_style(&self, c: &Comparison, regression: bool) -> String {
+        if c.diff_ns == 0 {
+            return String::new();
+        }
+        let light = self.light_theme();
+        let color = match (&self.flag_palette, regression) {
+            (&Palette::Default, true) => 'r',
+            (&Palette::Default, false) => 'g',
+            (&Palette::Colorblind, true) if light => 'm',
+            (&Palette::Colorblind, true) => 'y',
+            (&Palette::Colorblind, false) => 'b',
+        };
+        match self.flag_alarm.map_or(false, |a| c.diff_ratio.abs() * 100f64 >= a as f64) {
+            true if light => format!("bF{}", color),
+            true => format!("bF{}", color.to_ascii_uppercase()),
+            false => format!("F{}", color),
+        }
+    }
+
+    /// Returns whether the rows are colored for a light background.
+    fn light_theme(&self) -> bool {
+        match self.flag_theme {
+            Theme::Dark => false,
+            Theme::Light => true,
+            Theme::Auto => env::var("COLORFGBG").ok().map_or(false, |v| Args::light_background(&v)),
+        }
+    }
+
+    /// Returns whether a `$COLORFGBG` value, like `15;0` for white on black,
+    /// has a light background. Its last field is the background color, of
+    /// which only white (7) and bright white (15) are light.
+    fn light_background(colorfgbg: &str) -> bool {
+        match colorfgbg.rsplit(';').next().map(str::parse::<u8>) {
+            Some(Ok(bg)) => bg == 7 || bg == 15,
+            _ => false,
+        }
+    }
+
+    /// Parse benchmarks from the command line invocation given, along with
+    /// the names of the benchmarks parsed without a valid variance.
+    fn parse_benchmarks(&self) -> Result<(Benchmarks, Vec<String>)> {
+        let mut no_variance = vec![];
+        let (mut b_old, mut b_new) = if self.cmd_compare {
+            (try!(Args::load_baseline(&self.arg_name)), try!(self.parse_input(&mut no_variance)))
+        } else if let Some(ref one_file) = self.arg_file {
             if one_file == "-" {
-                let stdin = io::stdin();
-                let stdin_lock = stdin.lock();
-                let benches = try!(Args::parse_buffer(stdin_lock));
-                Ok(Benchmarks::from(Args::split_benchmarks(benches, &self.arg_old, &self.arg_new)))
+                let benches = try!(self.parse_stdin(&mut no_variance));
+                try!(self.split_input(benches, STDIN_NAME))
             } else {
-                self.parse_file_benchmarks(one_file)
+                try!(self.parse_file_benchmarks(one_file, &mut no_variance))
             }
         } else {
-            self.parse_old_new_benchmarks()
+            try!(self.parse_old_new_benchmarks(&mut no_variance))
+        };
+        if let Some(ref percentile) = self.flag_metric {
+            b_old = self.at_percentile(b_old, percentile, "old");
+            b_new = self.at_percentile(b_new, percentile, "new");
+        }
+        if self.flag_demangle {
+            for bench in b_old.iter_mut().chain(b_new.iter_mut()) {
+                bench.name = Args::demangle(&bench.name);
+            }
+        }
+        if self.flag_by_test_leaf {
+            b_old = self.leaf_benchmarks(b_old, "old");
+            b_new = self.leaf_benchmarks(b_new, "new");
+        }
+        if let Some(ref path) = self.flag_rename_map {
+            let renames = try!(Args::read_rename_map(path));
+            for bench in &mut b_old {
+                if let Some(new_name) = renames.get(&bench.name) {
+                    bench.name = new_name.clone();
+                }
+            }
+        }
+        if let Some(ref exclude) = self.flag_exclude {
+            let exclude = try!(Regex::new(exclude));
+            b_old.retain(|b| !exclude.is_match(&b.name));
+            b_new.retain(|b| !exclude.is_match(&b.name));
+        }
+        if let Some(reducer) = self.flag_reduce {
+            b_old = benchmark::reduce(b_old, reducer);
+            b_new = benchmark::reduce(b_new, reducer);
+        }
+        if self.best_baseline() {
+            b_old.extend(b_new.iter().cloned());
+            b_old = benchmark::reduce(b_old, Reduce::Min);
         }
+        Ok((Benchmarks::from((b_old, b_new)), no_variance))
+    }
+
+    /// Parses benchmarks from <file>, or from stdin if it is missing or `-`.
+    fn parse_input(&self, no_variance: &mut Vec<String>) -> Result<Vec<Benchmark>> {
+        match self.arg_file {
+            Some(ref file) if file != "-" => self.parse_runs(file, no_variance),
+            _ => self.parse_stdin(no_variance),
+        }
+    }
+
+    /// Loads the benchmarks saved as the baseline `name`.
+    fn load_baseline(name: &str) -> Result<Vec<Benchmark>> {
+        let mut json = String::new();
+        try!(try!(open_file(Args::baseline_path(name))).read_to_string(&mut json));
+        Ok(try!(json::decode(&json)))
+    }
+
+    /// Returns the path of the file the baseline `name` is saved in.
+    fn baseline_path(name: &str) -> PathBuf {
+        Path::new(BASELINE_DIR).join(format!("{}.json", name))
     }
 
     /// Parses benchmarks from two files: one containing old benchmark output
     /// and another containing new benchmark output.
-    fn parse_old_new_benchmarks(&self) -> Result<Benchmarks> {
-        let b_old = try!(Args::parse_buffer(io::BufReader::new(try!(open_file(&self.arg_old)))));
-        let b_new = try!(Args::parse_buffer(io::BufReader::new(try!(open_file(&self.arg_new)))));
+    fn parse_old_new_benchmarks(&self,
+                                no_variance: &mut Vec<String>)
+                                -> Result<(Vec<Benchmark>, Vec<Benchmark>)> {
+        let b_old = match self.flag_history {
+            Some(window) => try!(self.parse_history(&self.arg_old, window, no_variance)),
+            None => try!(self.parse_runs(&self.arg_old, no_variance)),
+        };
+        let b_new = try!(self.parse_runs(&self.arg_new, no_variance));
 
-        Ok(Benchmarks::from((b_old, b_new)))
+        Ok((b_old, b_new))
     }
 
     /// Parses benchmarks from one file, then splits on the two prefixes.
     /// See also: Args::split_benchmarks
-    fn parse_file_benchmarks<P>(&self, file: P) -> Result<Benchmarks>
-        where P: AsRef<Path>
-    {
-        let benches = try!(Args::parse_buffer(io::BufReader::new(try!(File::open(file)))));
-        Ok(Benchmarks::from(Args::split_benchmarks(benches, &self.arg_old, &self.arg_new)))
+    fn parse_file_benchmarks(&self,
+                             file: &str,
+                             no_variance: &mut Vec<String>)
+                             -> Result<(Vec<Benchmark>, Vec<Benchmark>)> {
+        let benches = try!(self.parse_runs(file, no_variance));
+        self.split_input(benches, file)
+    }
+
+    /// Splits benchmarks from one source on the two prefixes, failing if
+    /// either prefix matches none of the benchmarks.
+    /// See also: Args::split_benchmarks
+    fn split_input(&self,
+                   benches: Vec<Benchmark>,
+                   source: &str)
+                   -> Result<(Vec<Benchmark>, Vec<Benchmark>)> {
+        let (b_old, b_new) = Args::split_benchmarks(benches, &self.arg_old, &self.arg_new);
+        for &(ref benches, prefix) in &[(&b_old, &self.arg_old), (&b_new, &self.arg_new)] {
+            if benches.is_empty() {
+                return Err(Error::NoBenchmarks(format!("{} with prefix {}", source, prefix)));
+            }
+        }
+        Ok((b_old, b_new))
+    }
+
+    /// Parses benchmarks from a file. With --avg, `files` is a comma
+    /// separated list of files instead, and the benchmarks found in them are
+    /// combined per name, by the --reduce function if given.
+    fn parse_runs(&self, files: &str, no_variance: &mut Vec<String>) -> Result<Vec<Benchmark>> {
+        if !self.flag_avg {
+            return self.parse_file(files, no_variance);
+        }
+        let mut benches = vec![];
+        for file in files.split(',') {
+            benches.extend(try!(self.parse_file(file, no_variance)));
+        }
+        Ok(benchmark::reduce(benches, self.flag_reduce.unwrap_or(Reduce::Mean)))
+    }
+
+    /// Parses the last `window` runs in the directory `dir` and combines them
+    /// per benchmark, by the --reduce function if given and the mean if not.
+    /// Every benchmark file in the directory is one run.
+    fn parse_history(&self,
+                     dir: &str,
+                     window: usize,
+                     no_variance: &mut Vec<String>)
+                     -> Result<Vec<Benchmark>> {
+        if !Path::new(dir).is_dir() {
+            return Err(Error::NoHistory(dir.to_string()));
+        }
+        let mut paths = vec![];
+        try!(Args::bench_files(Path::new(dir), &mut paths));
+        paths.sort();
+        let skip = paths.len().saturating_sub(window);
+        let mut benches = vec![];
+        for (i, path) in paths[skip..].iter().enumerate() {
+            self.progress(i, paths.len() - skip);
+            let buffer = io::BufReader::new(try!(open_file(path)));
+            benches.extend(try!(self.parse_reader(buffer,
+                                                  &path.display().to_string(),
+                                                  no_variance)));
+        }
+        self.progress(paths.len() - skip, paths.len() - skip);
+        if benches.is_empty() {
+            return Err(Error::NoBenchmarks(dir.to_string()));
+        }
+        Ok(benchmark::reduce(benches, self.flag_reduce.unwrap_or(Reduce::Mean)))
+    }
+
+    /// Parses benchmarks from a file, failing if it contains none.
+    ///
+    /// If the file is a directory, the benchmarks of all benchmark files in
+    /// it and its subdirectories are parsed, in the order of their paths.
+    fn parse_file(&self, file: &str, no_variance: &mut Vec<String>) -> Result<Vec<Benchmark>> {
+        let mut paths = vec![];
+        if Path::new(file).is_dir() {
+            try!(Args::bench_files(Path::new(file), &mut paths));
+            paths.sort();
+        } else {
+            paths.push(PathBuf::from(file));
+        }
+        let mut benches = vec![];
+        let total = paths.len();
+        for (i, path) in paths.into_iter().enumerate() {
+            self.progress(i, total);
+            let buffer = io::BufReader::new(try!(open_file(&path)));
+            benches.extend(try!(self.parse_reader(buffer,
+                                                  &path.display().to_string(),
+                                                  no_variance)));
+        }
+        self.progress(total, total);
+        if benches.is_empty() {
+            return Err(Error::NoBenchmarks(file.to_string()));
+        }
+        Ok(benches)
+    }
+
+    /// Shows on stderr that `done` out of `total` files have been read, for
+    /// --progress, and clears the line again once all are. Nothing is shown
+    /// for single files, with --quiet or if stderr is not a terminal.
+    fn progress(&self, done: usize, total: usize) {
+        if !self.flag_progress || self.flag_quiet || total < 2 || !terminal::stderr_is_tty() {
+            return;
+        }
+        let stderr = io::stderr();
+        let mut err = stderr.lock();
+        let _ = if done < total {
+            write!(err, "\r\x1b[Kreading files: {}/{}", done, total)
+        } else {
+            write!(err, "\r\x1b[K")
+        };
+        let _ = err.flush();
+    }
+
+    /// Collects the .txt and .bench files in a directory tree.
+    fn bench_files(dir: &Path, paths: &mut Vec<PathBuf>) -> Result<()> {
+        for entry in try!(fs::read_dir(dir)) {
+            let path = try!(entry).path();
+            if path.is_dir() {
+                try!(Args::bench_files(&path, paths));
+            } else if path.extension().map_or(false, |ext| ext == "txt" || ext == "bench") {
+                paths.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses benchmarks from stdin, failing if it contains none.
+    fn parse_stdin(&self, no_variance: &mut Vec<String>) -> Result<Vec<Benchmark>> {
+        let stdin = io::stdin();
+        let benches = try!(self.parse_reader(stdin.lock(), STDIN_NAME, no_variance));
+        if benches.is_empty() {
+            return Err(Error::NoBenchmarks(STDIN_NAME.to_string()));
+        }
+        Ok(benches)
+    }
+
+    /// Parses benchmarks from a buffered reader in the --input-format.
+    /// `source` names the reader in errors. The names of benchmarks without a
+    /// valid variance are added to `no_variance`.
+    fn parse_reader<B: BufRead>(&self,
+                                mut buffer: B,
+                                source: &str,
+                                no_variance: &mut Vec<String>)
+                                -> Result<Vec<Benchmark>> {
+        match self.flag_input_format {
+            InputFormat::Text => {
+                Args::parse_buffer(buffer, self.flag_by_throughput, no_variance)
+            }
+            InputFormat::JsonArray => {
+                let mut report = String::new();
+                try!(buffer.read_to_string(&mut report));
+                let benches = try!(Args::parse_json_report(&report, source));
+                if self.flag_by_throughput {
+                    Ok(benches.into_iter().filter_map(Benchmark::into_throughput).collect())
+                } else {
+                    Ok(benches)
+                }
+            }
+        }
+    }
+
+    /// Parses benchmarks from a JSON document with a `benchmarks` array.
+    ///
+    /// Every benchmark in it is an object with a `name` string and an `ns`
+    /// number, the ns/iter. The `variance`, `throughput` in MB/s and
+    /// `iterations` numbers are optional, where a missing variance is 0. So is
+    /// a `percentiles` object mapping names like `p95` to the ns/iter at that
+    /// percentile, for use with --metric. Other fields are ignored.
+    fn parse_json_report(report: &str, source: &str) -> Result<Vec<Benchmark>> {
+        let invalid = |msg: String| {
+            Error::JsonReport {
+                source: source.to_string(),
+                msg: msg,
+            }
+        };
+        let report = try!(Json::from_str(report).map_err(|e| invalid(e.to_string())));
+        let elems = match report.find("benchmarks").and_then(Json::as_array) {
+            Some(elems) => elems,
+            None => return Err(invalid("expected an object with a benchmarks array".to_string())),
+        };
+        let as_ns = |n: &Json| {
+            n.as_u64().or_else(|| n.as_f64().and_then(|f| if f >= 0f64 {
+                Some(f.round() as u64)
+            } else {
+                None
+            }))
+        };
+        let number = |elem: &Json, field: &str| elem.find(field).and_then(&as_ns);
+        let mut benches = vec![];
+        for (i, elem) in elems.iter().enumerate() {
+            let name = match elem.find("name").and_then(Json::as_string) {
+                Some(name) => name,
+                None => return Err(invalid(format!("benchmark {} has no name string", i))),
+            };
+            let ns = match number(elem, "ns") {
+                Some(ns) => ns,
+                None => return Err(invalid(format!("benchmark {} has no ns number", name))),
+            };
+            let percentiles = match elem.find("percentiles") {
+                None => None,
+                Some(&Json::Object(ref percentiles)) => {
+                    let mut map = BTreeMap::new();
+                    for (p, n) in percentiles {
+                        match as_ns(n) {
+                            Some(ns) => map.insert(p.clone(), ns),
+                            None => {
+                                return Err(invalid(format!("percentile {} of benchmark {} is \
+                                                            not a number",
+                                                           p,
+                                                           name)))
+                            }
+                        };
+                    }
+                    Some(map)
+                }
+                Some(_) => {
+                    return Err(invalid(format!("the percentiles of benchmark {} are not an \
+                                                object",
+                                               name)))
+                }
+            };
+            benches.push(Benchmark {
+                name: name.to_string(),
+                ns: ns,
+                variance: number(elem, "variance").unwrap_or(0),
+                throughput: number(elem, "throughput"),
+                iterations: number(elem, "iterations").map(|n| n as usize),
+                percentiles: percentiles,
+            });
+        }
+        Ok(benches)
     }
 
     /// Parse benchmarks from a buffered reader.
-    fn parse_buffer<B: BufRead>(buffer: B) -> Result<Vec<Benchmark>> {
+    ///
+    /// Test result lines from combined `cargo test` and `cargo bench` output
+    /// are skipped before trying to parse a line as a benchmark.
+    ///
+    /// If `by_throughput`, the benchmarks are parsed with their throughput
+    /// in place of their ns/iter. See `Benchmark::parse_throughput`.
+    ///
+    /// The names of benchmarks without a valid variance, which is taken to be
+    /// 0, are added to `no_variance`.
+    fn parse_buffer<B: BufRead>(buffer: B,
+                                by_throughput: bool,
+                                no_variance: &mut Vec<String>)
+                                -> Result<Vec<Benchmark>> {
         let iter = buffer.lines();
         let mut vec = Vec::with_capacity(iter.size_hint().0);
         for result in iter {
-            if let Ok(bench) = try!(result).parse() {
+            let line = try!(result);
+            if Args::is_test_result(&line) {
+                continue;
+            }
+            if by_throughput {
+                vec.extend(Benchmark::parse_throughput(&line));
+            } else if let Ok(bench) = line.parse::<Benchmark>() {
+                if benchmark::lacks_variance(&line) {
+                    no_variance.push(bench.name.clone());
+                }
                 vec.push(bench)
             }
         }
         Ok(vec)
     }
 
+    /// Returns whether a line is the result of a test, e.g.
+    /// `test foo ... ok`, or the summary of a test run.
+    fn is_test_result(line: &str) -> bool {
+        let line = line.trim();
+        line.starts_with("test result:") ||
+        line.starts_with("test ") &&
+        [" ... ok", " ... FAILED", " ... ignored"].iter().any(|end| line.ends_with(end))
+    }
+
     /// Splits benchmarks from one source with two prefixes. The first prefix
     /// identifies benchmarks in the old set and the second prefix identifies
     /// benchmarks in the new set where all benchmarks are found in one file.
@@ -209,15 +1951,133 @@ impl Args {
         (b_old, b_new)
     }
 
+    /// Renames every benchmark to its test leaf, so that benchmarks are
+    /// compared regardless of the module they live in. Distinct benchmarks
+    /// that end up with the same name are reported with a warning.
+    fn leaf_benchmarks(&self, vec: Vec<Benchmark>, which: &str) -> Vec<Benchmark> {
+        let mut originals: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        let vec = vec.into_iter()
+            .map(|mut bench| {
+                let leaf = Args::test_leaf(&bench.name, &self.flag_separator_module).to_string();
+                originals.entry(leaf.clone()).or_insert_with(Vec::new).push(bench.name);
+                bench.name = leaf;
+                bench
+            })
+            .collect();
+        for (leaf, names) in originals {
+            if names.len() > 1 && !self.flag_quiet {
+                eprintln!("WARNING: benchmarks in {} share the test name {}: {}",
+                          which,
+                          leaf,
+                          names.join(", "));
+            }
+        }
+        vec
+    }
+
+    /// Puts the ns/iter at the --metric percentile in place of the ns/iter of
+    /// the benchmarks, leaving out and warning about those without it.
+    fn at_percentile(&self, vec: Vec<Benchmark>, percentile: &str, which: &str) -> Vec<Benchmark> {
+        let mut missing = vec![];
+        let vec = vec.into_iter()
+            .filter_map(|bench| {
+                let name = bench.name.clone();
+                let at = bench.at_percentile(percentile);
+                if at.is_none() {
+                    missing.push(name);
+                }
+                at
+            })
+            .collect();
+        if !missing.is_empty() && !self.flag_quiet {
+            eprintln!("WARNING: benchmarks in {} without {}: {}",
+                      which,
+                      percentile,
+                      missing.join(", "));
+        }
+        vec
+    }
+
+    /// Returns the name a benchmark is paired on with --match-pattern: the
+    /// capture groups of the pattern in the name, or the whole match if it
+    /// has none. Names that don't match are paired on the name itself.
+    fn match_key(name: &str, pattern: &Regex) -> String {
+        match pattern.captures(name) {
+            None => name.to_string(),
+            Some(ref caps) if caps.len() == 1 => caps.at(0).unwrap_or("").to_string(),
+            Some(caps) => {
+                // Keep the groups apart so that ("ab", "c") and ("a", "bc")
+                // give different names.
+                caps.iter().skip(1).map(|cap| cap.unwrap_or("")).collect::<Vec<_>>().join("\0")
+            }
+        }
+    }
+
+    /// Returns the name a benchmark is paired on with --normalize-names.
+    fn normalize_name(name: &str) -> String {
+        name.trim().to_lowercase()
+    }
+
+    /// Removes compiler generated closure and hash segments from a benchmark
+    /// name.
+    fn demangle(name: &str) -> String {
+        MANGLING_REGEX.replace_all(name, "")
+    }
+
+    /// Returns the last `sep`-separated segment of a benchmark name.
+    fn test_leaf<'a>(name: &'a str, sep: &str) -> &'a str {
+        if sep.is_empty() {
+            return name;
+        }
+        name.rsplit(sep).next().unwrap_or(name)
+    }
+
+    /// Reads the renames of old benchmarks from a --rename-map file.
+    fn read_rename_map(path: &str) -> Result<BTreeMap<String, String>> {
+        let mut text = String::new();
+        try!(try!(open_file(path)).read_to_string(&mut text));
+        Args::parse_rename_map(path, &text)
+    }
+
+    /// Parses a rename map with an `old_name=new_name` pair on every line.
+    /// Empty lines and lines starting with `#` are skipped.
+    fn parse_rename_map(path: &str, text: &str) -> Result<BTreeMap<String, String>> {
+        let mut renames = BTreeMap::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut names = line.splitn(2, '=').map(str::trim);
+            match (names.next(), names.next()) {
+                (Some(old), Some(new)) if !old.is_empty() && !new.is_empty() => {
+                    renames.insert(old.to_string(), new.to_string());
+                }
+                _ => {
+                    return Err(Error::RenameMap {
+                        path: path.into(),
+                        line: i + 1,
+                    })
+                }
+            }
+        }
+        Ok(renames)
+    }
+
     /// Returns the names that should be used in the column header.
     fn names(arg_old: &str, arg_new: &str) -> (String, String) {
         // If either of the names are empty, substitute them with defaults.
-        let arg_old = if arg_old.is_empty() {
+        // So are process substitutions like <(cargo bench), which are read
+        // from paths like /dev/fd/63 that say nothing about the input.
+        let unnamed = |arg: &str| {
+            arg.is_empty() || arg.starts_with("/dev/fd/") || arg.starts_with("/proc/self/fd/")
+        };
+        let arg_old = if unnamed(arg_old) {
             "old".to_string()
         } else {
             arg_old.to_string()
         };
-        let arg_new = if arg_new.is_empty() {
+        let arg_new = if unnamed(arg_new) {
             "new".to_string()
         } else {
             arg_new.to_string()
@@ -272,6 +2132,73 @@ fn open_file<P: AsRef<Path>>(path: P) -> Result<File> {
     })
 }
 
+/// Renders a table as plain text without borders or styles: the first
+/// column is padded on the right and the other columns are right aligned,
+/// all separated by two spaces.
+fn plain(table: &Table) -> String {
+    let rows: Vec<Vec<String>> = table.row_iter()
+        .map(|row| row.iter().map(|cell| cell.get_content()).collect())
+        .collect();
+    let mut widths = vec![];
+    for row in &rows {
+        for (i, cell) in row.iter().enumerate() {
+            let width = cell.chars().count();
+            if i == widths.len() {
+                widths.push(width);
+            } else if width > widths[i] {
+                widths[i] = width;
+            }
+        }
+    }
+    let mut out = String::new();
+    for row in &rows {
+        let line = row.iter()
+            .zip(&widths)
+            .enumerate()
+            .map(|(i, (cell, &width))| if i == 0 {
+                format!("{:<1$}", cell, width)
+            } else {
+                format!("{:>1$}", cell, width)
+            })
+            .collect::<Vec<String>>()
+            .join("  ");
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+/// The commands tried, in order, to copy text to the system clipboard.
+const CLIPBOARD_COMMANDS: &'static [&'static [&'static str]] = &[&["pbcopy"],
+                                                                &["clip"],
+                                                                &["wl-copy"],
+                                                                &["xclip", "-selection", "clipboard"],
+                                                                &["xsel", "--clipboard", "--input"]];
+
+/// Copies the text to the system clipboard with the first of the
+/// `CLIPBOARD_COMMANDS` that is available.
+fn copy_to_clipboard(text: &[u8]) -> Result<()> {
+    for cmd in CLIPBOARD_COMMANDS {
+        let child = process::Command::new(cmd[0])
+            .args(&cmd[1..])
+            .stdin(process::Stdio::piped())
+            .stdout(process::Stdio::null())
+            .stderr(process::Stdio::null())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(_) => continue,
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            try!(stdin.write_all(text));
+        }
+        if try!(child.wait()).success() {
+            return Ok(());
+        }
+    }
+    Err(Error::NoClipboard)
+}
+
 #[cfg(test)]
 mod tests {
     use quickcheck::Arbitrary;
@@ -415,6 +2342,297 @@ mod tests {
                 shortest_difference
             }
         }
+
+        #[test]
+        fn process_substitutions() {
+            assert_eq!(Args::names("/dev/fd/63", "/dev/fd/62"),
+                       ("old".to_string(), "new".to_string()));
+            assert_eq!(Args::names("/proc/self/fd/11", "new.txt"),
+                       ("old".to_string(), "new.txt".to_string()));
+        }
+    }
+
+    #[cfg(unix)]
+    mod light_background {
+        use super::super::Args;
+
+        #[test]
+        fn background_is_last() {
+            assert!(Args::light_background("0;15"));
+            assert!(Args::light_background("0;default;7"));
+            assert!(!Args::light_background("15;0"));
+            assert!(!Args::light_background("7;8"));
+        }
+
+        #[test]
+        fn unknown_is_dark() {
+            assert!(!Args::light_background(""));
+            assert!(!Args::light_background("default;default"));
+        }
+    }
+
+    #[cfg(unix)]
+    mod parse_file {
+        use std::env;
+        use std::ffi::CString;
+        use std::fs::{self, OpenOptions};
+        use std::io::Write;
+        use std::process;
+        use std::thread;
+        use std::time::Duration;
+
+        use super::super::parse_args;
+
+        #[test]
+        fn slow_fifo() {
+            let path = env::temp_dir().join(format!("cargo-benchcmp-slow-fifo-{}", process::id()));
+            let _ = fs::remove_file(&path);
+            let c_path = CString::new(path.to_str().unwrap()).unwrap();
+            assert_eq!(unsafe { ::libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+            let writer_path = path.clone();
+            let writer = thread::spawn(move || {
+                let mut fifo = OpenOptions::new().write(true).open(writer_path).unwrap();
+                for line in &["",
+                              "running 2 tests",
+                              "test a ... bench:         349 ns/iter (+/- 5)",
+                              "test b ... bench:       3,125 ns/iter (+/- 13)"] {
+                    writeln!(fifo, "{}", line).unwrap();
+                    thread::sleep(Duration::from_millis(20));
+                }
+            });
+            let argv = vec!["cargo-benchcmp", "benchcmp", "old", "new"];
+            let args = parse_args(argv.into_iter().map(str::to_string).collect());
+            let benches = args.parse_file(path.to_str().unwrap(), &mut vec![]);
+            writer.join().unwrap();
+            fs::remove_file(&path).unwrap();
+            let ns = benches.unwrap().iter().map(|b| b.ns).collect::<Vec<u64>>();
+            assert_eq!(ns, vec![349, 3125]);
+        }
+    }
+
+    mod parse_buffer {
+        use super::super::Args;
+
+        #[test]
+        fn skips_test_results() {
+            let output = "\nrunning 5 tests
+test tests::parses ... ok
+test tests::bench_like_name ... FAILED
+test tests::slow ... ignored
+test bench_a ... bench:       1,234 ns/iter (+/- 56)
+test bench_b ... bench:          78 ns/iter (+/- 9) = 100 MB/s
+
+test result: FAILED. 1 passed; 1 failed; 1 ignored; 2 measured
+";
+            let benches = Args::parse_buffer(output.as_bytes(), false, &mut vec![]).unwrap();
+            let names: Vec<&str> = benches.iter().map(|b| &*b.name).collect();
+            assert_eq!(names, vec!["bench_a", "bench_b"]);
+        }
+
+        #[test]
+        fn notes_missing_variance() {
+            let output = "test bench_a ... bench:       1,234 ns/iter (+/- NaN)
+test bench_b ... bench:          78 ns/iter (+/- 9)
+";
+            let mut no_variance = vec![];
+            Args::parse_buffer(output.as_bytes(), false, &mut no_variance).unwrap();
+            assert_eq!(no_variance, vec!["bench_a".to_string()]);
+        }
+
+        #[test]
+        fn test_results() {
+            assert!(Args::is_test_result("test foo ... ok"));
+            assert!(Args::is_test_result("test foo::bar ... FAILED"));
+            assert!(Args::is_test_result("test foo ... ignored"));
+            assert!(Args::is_test_result("test result: ok. 3 passed; 0 failed"));
+            assert!(!Args::is_test_result("test foo ... bench: 1 ns/iter (+/- 0)"));
+        }
+    }
+
+    mod demangle {
+        use super::super::Args;
+
+        #[test]
+        fn closures_and_hashes() {
+            assert_eq!(Args::demangle("parse::{{closure}}"), "parse");
+            assert_eq!(Args::demangle("a::{{closure}}::{{closure}}::b"), "a::b");
+            assert_eq!(Args::demangle("parse::{closure#0}"), "parse");
+            assert_eq!(Args::demangle("bench::parse::h0123456789abcdef"), "bench::parse");
+            assert_eq!(Args::demangle("bench::hash_map"), "bench::hash_map");
+        }
+    }
+
+    mod match_key {
+        use regex::Regex;
+
+        use super::super::Args;
+
+        #[test]
+        fn capture_groups() {
+            let pattern = Regex::new(r"(\w+)_v[0-9]+::(\w+)").unwrap();
+            assert_eq!(Args::match_key("foo_v1::bench", &pattern),
+                       Args::match_key("foo_v2::bench", &pattern));
+            assert!(Args::match_key("foo_v1::bench", &pattern) !=
+                    Args::match_key("bar_v1::bench", &pattern));
+            assert_eq!(Args::match_key("unversioned", &pattern), "unversioned");
+        }
+
+        #[test]
+        fn whole_match_without_groups() {
+            let pattern = Regex::new(r"[a-z]+$").unwrap();
+            assert_eq!(Args::match_key("v1::parse", &pattern), "parse");
+        }
+    }
+
+    mod test_leaf {
+        use super::super::Args;
+        use super::AlphaString;
+
+        quickcheck! {
+            fn leaf_has_no_module(path: Vec<AlphaString>, leaf: AlphaString) -> bool {
+                let AlphaString(leaf) = leaf;
+                let mut name = path.into_iter()
+                    .map(|AlphaString(s)| s)
+                    .collect::<Vec<String>>()
+                    .join("::");
+                if !name.is_empty() {
+                    name.push_str("::");
+                }
+                name.push_str(&leaf);
+
+                Args::test_leaf(&name, "::") == leaf
+            }
+        }
+
+        #[test]
+        fn other_separators() {
+            assert_eq!(Args::test_leaf("group/sub/parse", "/"), "parse");
+            assert_eq!(Args::test_leaf("group.parse", "."), "parse");
+            assert_eq!(Args::test_leaf("group::parse", "/"), "group::parse");
+            assert_eq!(Args::test_leaf("group::parse", ""), "group::parse");
+        }
+    }
+
+    mod plain {
+        use prettytable::Table;
+        use prettytable::cell::Cell;
+        use prettytable::row::Row;
+
+        use super::super::plain;
+
+        #[test]
+        fn aligns_columns() {
+            let mut table = Table::new();
+            table.add_row(Row::new(vec![Cell::new("name"), Cell::new("ns/iter")]));
+            table.add_row(Row::new(vec![Cell::new("a_longer_name"), Cell::new("1")]));
+            table.add_row(Row::new(vec![Cell::new("b"), Cell::new("1,000")]));
+            assert_eq!(plain(&table),
+                       "name           ns/iter\na_longer_name        1\nb                1,000\n");
+        }
+    }
+
+    mod fit_names {
+        use prettytable::Table;
+        use prettytable::cell::Cell;
+        use prettytable::row::Row;
+
+        use benchmark::{Column, RowFormat};
+        use super::super::Args;
+
+        #[test]
+        fn shortens_long_names() {
+            let fmt = RowFormat {
+                columns: vec![Column::Name, Column::DiffPct],
+                variance: false,
+                bounds: false,
+                iterations: false,
+                precision: 2,
+                round: None,
+                separator: ",".to_string(),
+                relative: false,
+                arrows: false,
+                ascii: false,
+            };
+            let mut table = Table::new();
+            table.add_row(Row::new(vec![Cell::new("name"), Cell::new("diff %")]));
+            table.add_row(Row::new(vec![Cell::new("a_rather_long_benchmark_name"),
+                                        Cell::new("1.00%")]));
+            table.add_row(Row::new(vec![Cell::new("short"), Cell::new("2.00%")]));
+            Args::fit_names(&mut table, &fmt, &["".to_string(), "".to_string()], 24);
+            let names: Vec<String> = table.column_iter(0).map(|c| c.get_content()).collect();
+            assert_eq!(names, vec!["name", "a_rather_long…", "short"]);
+        }
+    }
+
+    mod parse_json_report {
+        use super::super::Args;
+
+        #[test]
+        fn benchmarks_array() {
+            let report = r#"{"version": 2, "benchmarks": [
+                {"name": "a", "ns": 1234, "variance": 56, "iterations": 100},
+                {"name": "b", "ns": 78.4, "throughput": 100}
+            ]}"#;
+            let benches = Args::parse_json_report(report, "report").unwrap();
+            assert_eq!(benches.len(), 2);
+            assert_eq!((&*benches[0].name, benches[0].ns, benches[0].variance), ("a", 1234, 56));
+            assert_eq!(benches[0].iterations, Some(100));
+            assert_eq!((&*benches[1].name, benches[1].ns, benches[1].variance), ("b", 78, 0));
+            assert_eq!(benches[1].throughput, Some(100));
+        }
+
+        #[test]
+        fn missing_fields() {
+            assert!(Args::parse_json_report(r#"[{"name": "a", "ns": 1}]"#, "report").is_err());
+            assert!(Args::parse_json_report(r#"{"benchmarks": [{"ns": 1}]}"#, "report").is_err());
+            assert!(Args::parse_json_report(r#"{"benchmarks": [{"name": "a"}]}"#, "report")
+                .is_err());
+        }
+    }
+
+    mod parse_rename_map {
+        use super::super::Args;
+
+        #[test]
+        fn pairs_and_comments() {
+            let renames = Args::parse_rename_map("map", "# renamed in 0.2\n\nold_a = new_a\nb=c\n")
+                .unwrap();
+            assert_eq!(renames.len(), 2);
+            assert_eq!(renames["old_a"], "new_a");
+            assert_eq!(renames["b"], "c");
+        }
+
+        #[test]
+        fn missing_new_name() {
+            assert!(Args::parse_rename_map("map", "a=b\nc\n").is_err());
+            assert!(Args::parse_rename_map("map", "a=\n").is_err());
+        }
+    }
+
+    mod emits {
+        use super::super::{parse_args, Format};
+
+        fn emits(emit: &[&str]) -> Vec<(Format, String)> {
+            let mut argv = vec!["cargo-benchcmp", "benchcmp"];
+            for emit in emit {
+                argv.extend(&["--emit", emit]);
+            }
+            argv.extend(&["old", "new"]);
+            parse_args(argv.into_iter().map(str::to_string).collect()).emits().unwrap()
+        }
+
+        #[test]
+        fn windows_paths() {
+            let emits = emits(&[r"csv:C:\bench reports\out.csv", r"json:\\server\share\out.json"]);
+            assert_eq!(emits[0], (Format::Csv, r"C:\bench reports\out.csv".to_string()));
+            assert_eq!(emits[1], (Format::Json, r"\\server\share\out.json".to_string()));
+        }
+
+        #[test]
+        fn commas_in_paths() {
+            let emits = emits(&["csv:report,old.csv"]);
+            assert_eq!(emits, vec![(Format::Csv, "report,old.csv".to_string())]);
+        }
     }
 
     mod split_benchmarks {
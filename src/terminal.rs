@@ -0,0 +1,37 @@
+/// Returns the width of the terminal stdout is connected to, or `None` if
+/// stdout is not a terminal.
+#[cfg(unix)]
+pub fn width() -> Option<usize> {
+    use std::mem;
+
+    unsafe {
+        if ::libc::isatty(::libc::STDOUT_FILENO) == 0 {
+            return None;
+        }
+        let mut size: ::libc::winsize = mem::zeroed();
+        if ::libc::ioctl(::libc::STDOUT_FILENO, ::libc::TIOCGWINSZ as _, &mut size) == -1 ||
+           size.ws_col == 0 {
+            return None;
+        }
+        Some(size.ws_col as usize)
+    }
+}
+
+/// Returns the width of the terminal stdout is connected to, or `None` if
+/// stdout is not a terminal.
+#[cfg(not(unix))]
+pub fn width() -> Option<usize> {
+    None
+}
+
+/// Returns whether stderr is connected to a terminal.
+#[cfg(unix)]
+pub fn stderr_is_tty() -> bool {
+    unsafe { ::libc::isatty(::libc::STDERR_FILENO) != 0 }
+}
+
+/// Returns whether stderr is connected to a terminal.
+#[cfg(not(unix))]
+pub fn stderr_is_tty() -> bool {
+    false
+}
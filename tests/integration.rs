@@ -33,6 +33,22 @@ fn same_input() {
     new_ucmd().args(&["bench_output_1.txt", "bench_output_1.txt"]).succeeds().stdout_is_fixture("same_input.expected");
 }
 
+#[test]
+fn same_input_warning() {
+    new_ucmd()
+        .args(&["bench_output_1.txt", "bench_output_1.txt"])
+        .succeeds()
+        .stderr_is("WARNING: old and new are the same input: bench_output_1.txt");
+}
+
+#[test]
+fn unchanged_input_warning() {
+    new_ucmd()
+        .args(&["bench_output_1.txt", "./bench_output_1.txt"])
+        .succeeds()
+        .stderr_is("WARNING: no benchmark changed at all, old and new may be the same input");
+}
+
 #[test]
 fn different_input() {
     new_ucmd()
@@ -51,6 +67,76 @@ fn non_overlapping_input() {
         .no_stdout();
 }
 
+#[test]
+fn crlf_input() {
+    // bench_output_16.txt is bench_output_3.txt with Windows line endings.
+    new_ucmd()
+        .args(&["--label-snd", "bench_output_3.txt", "bench_output_2.txt", "bench_output_16.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input.expected");
+}
+
+#[cfg(windows)]
+#[test]
+fn windows_drive_letter_paths() {
+    let mut scene: second_law::Scene = new_scene!();
+    scene.subcmd_arg("benchcmp");
+    let old = scene.working_dir().plus_as_string("bench_output_2.txt");
+    let new = scene.working_dir().plus_as_string("bench_output_3.txt");
+    assert_eq!(old.chars().nth(1), Some(':'));
+    scene.ucmd()
+        .args(&[&old, &new])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input.expected");
+}
+
+#[cfg(windows)]
+#[test]
+fn windows_backslash_directory() {
+    // shards holds the benchmarks of bench_output_2.txt split over two files.
+    new_ucmd()
+        .args(&["--label-fst", "bench_output_2.txt", r".\shards", "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input.expected");
+}
+
+#[test]
+fn quiet() {
+    new_ucmd()
+        .args(&["--quiet", "bench_output_1.txt", "bench_output_2.txt"])
+        .succeeds()
+        .no_stderr()
+        .no_stdout();
+}
+
+#[test]
+fn no_variance_note() {
+    new_ucmd()
+        .args(&["bench_output_2.txt", "bench_output_15.txt"])
+        .succeeds()
+        .stderr_is("NOTE: no valid variance for ac_one_byte, using 0");
+}
+
+#[test]
+fn no_variance_note_quiet() {
+    new_ucmd()
+        .args(&["--quiet", "bench_output_2.txt", "bench_output_15.txt"])
+        .succeeds()
+        .no_stderr();
+}
+
+#[test]
+fn list() {
+    new_ucmd()
+        .args(&["--list", "bench_output_2.txt", "bench_output_4.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("list.expected");
+}
+
 #[cfg(unix)]
 #[test]
 fn different_input_colored() {
@@ -65,6 +151,72 @@ fn different_input_colored() {
         .stdout_is_fixture("different_input_colored.expected");
 }
 
+#[cfg(unix)]
+#[test]
+fn different_input_alarm() {
+    let mut scene: second_law::Scene = new_scene!();
+    scene.subcmd_arg("benchcmp");
+    scene.ucmd_keepenv()
+        .args(&["--color", "always", "--alarm", "30", "bench_output_2.txt", "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_alarm.expected");
+}
+
+#[test]
+fn sections() {
+    new_ucmd()
+        .args(&["--sections", "bench_output_13.txt", "bench_output_14.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_sections.expected");
+}
+
+#[test]
+fn trend() {
+    // trend_old.json compares bench_output_2.txt with bench_output_3.txt, and
+    // trend_new.json the other way around, with a threshold of 30%.
+    new_ucmd()
+        .args(&["trend", "trend_old.json", "trend_new.json"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("trend.expected");
+}
+
+#[test]
+fn trend_json() {
+    new_ucmd()
+        .args(&["trend", "--format", "json", "trend_old.json", "trend_new.json"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("trend_json.expected");
+}
+
+#[cfg(unix)]
+#[test]
+fn different_input_light_theme() {
+    let mut scene: second_law::Scene = new_scene!();
+    scene.subcmd_arg("benchcmp");
+    scene.ucmd_keepenv()
+        .args(&["--color", "always", "--palette", "colorblind", "--theme", "light", "--alarm",
+                "50", "bench_output_2.txt", "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_light_theme.expected");
+}
+
+#[cfg(unix)]
+#[test]
+fn different_input_colorblind() {
+    let mut scene: second_law::Scene = new_scene!();
+    scene.subcmd_arg("benchcmp");
+    scene.ucmd_keepenv()
+        .args(&["--color", "always", "--palette", "colorblind", "bench_output_2.txt", "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_colorblind.expected");
+}
+
 #[test]
 fn different_input_selections() {
     new_ucmd()
@@ -74,6 +226,110 @@ fn different_input_selections() {
         .stdout_is_fixture("different_input_selections.expected");
 }
 
+#[test]
+fn directory() {
+    // shards holds the benchmarks of bench_output_2.txt split over two files
+    // in a directory tree, next to a file that isn't benchmark output.
+    new_ucmd()
+        .args(&["--label-fst", "bench_output_2.txt", "shards", "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input.expected");
+}
+
+#[test]
+#[cfg(windows)]
+fn windows_paths() {
+    // Backslashes, and a directory as in the directory test.
+    new_ucmd()
+        .args(&["--label-fst", "bench_output_2.txt", "--label-snd", "bench_output_3.txt",
+                r".\shards", r"shards\..\bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input.expected");
+}
+
+#[test]
+fn history() {
+    // history holds three runs, of which the oldest is outside the window.
+    new_ucmd()
+        .args(&["--history", "2", "history", "bench_output_12.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_history.expected");
+}
+
+#[test]
+fn history_not_a_directory() {
+    new_ucmd()
+        .args(&["--history", "2", "bench_output_2.txt", "bench_output_12.txt"])
+        .fails()
+        .stderr_is("--history needs <old> to be a directory of runs, not bench_output_2.txt");
+}
+
+#[test]
+fn explain() {
+    new_ucmd()
+        .args(&["--explain", "--threshold", "3", "--history", "2", "history",
+                "bench_output_12.txt"])
+        .succeeds()
+        .stderr_is("skipped ac_one_byte: 1.14% below threshold 3%\n\
+                    skipped ac_two_bytes: 0.32% below threshold 3%")
+        .stdout_is_fixture("different_input_explain.expected");
+}
+
+#[test]
+fn include_unchanged() {
+    new_ucmd()
+        .args(&["--include-unchanged", "--threshold", "3", "--history", "2", "history",
+                "bench_output_12.txt"])
+        .succeeds()
+        .stderr_is("2 unchanged within threshold")
+        .stdout_is_fixture("different_input_explain.expected");
+}
+
+#[test]
+fn fail_on_budget() {
+    new_ucmd()
+        .args(&["--budget", "ac_ten_bytes=60000,ac_one_byte=400", "--fail-on-budget",
+                "--threshold", "3", "--history", "2", "history", "bench_output_12.txt"])
+        .fails()
+        .stderr_is("OVER BUDGET: ac_ten_bytes takes 63012 ns/iter, 5.02% over its budget of \
+                    60000\n1 benchmarks are over budget")
+        .stdout_is_fixture("different_input_explain.expected");
+}
+
+#[test]
+fn fail_on_budget_and_regression() {
+    new_ucmd()
+        .args(&["--budget", "ac_ten_bytes=60000,ac_one_byte=400", "--fail-on-budget",
+                "--fail-on-regression", "3", "--threshold", "3", "--history", "2", "history",
+                "bench_output_12.txt"])
+        .fails()
+        .stderr_is("OVER BUDGET: ac_ten_bytes takes 63012 ns/iter, 5.02% over its budget of \
+                    60000\n1 benchmarks regressed by 3% or more\n1 benchmarks are over budget")
+        .stdout_is_fixture("different_input_explain.expected");
+}
+
+#[test]
+fn worst() {
+    new_ucmd()
+        .args(&["--worst", "bench_output_2.txt", "bench_output_3.txt"])
+        .fails()
+        .stderr_is("14 benchmarks regressed")
+        .stdout_is("ac_ten_bytes 84.50%\n");
+}
+
+#[test]
+fn worst_below_failing_percentage() {
+    new_ucmd()
+        .args(&["--worst", "--fail-on-regression", "90", "bench_output_2.txt",
+                "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is("ac_ten_bytes 84.50%\n");
+}
+
 #[test]
 fn stdin() {
     new_ucmd()
@@ -83,3 +339,724 @@ fn stdin() {
         .no_stderr()
         .stdout_is_fixture("different_input_selections.expected");
 }
+
+#[test]
+fn regressions() {
+    new_ucmd()
+        .args(&["--regressions", "bench_output_2.txt", "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input.expected");
+}
+
+#[test]
+fn improvements_leave_out_unchanged() {
+    new_ucmd()
+        .args(&["--quiet", "--format", "csv", "--improvements", "bench_output_2.txt",
+                "bench_output_15.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is("name,old_ns,new_ns,old_variance,new_variance,diff_ns,change_pct,direction\n");
+}
+
+#[test]
+fn bigger_is_better() {
+    new_ucmd()
+        .args(&["--bigger-is-better", "--improvements", "bench_output_2.txt", "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input.expected");
+}
+
+#[test]
+fn precision() {
+    new_ucmd()
+        .args(&["--precision", "4", "bench_output_2.txt", "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_precision.expected");
+}
+
+#[test]
+fn separator() {
+    new_ucmd()
+        .args(&["--separator", ".", "--threshold", "80", "bench_output_2.txt", "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_separator.expected");
+}
+
+#[test]
+fn compact() {
+    new_ucmd()
+        .args(&["--compact", "bench_output_2.txt", "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_compact.expected");
+}
+
+#[test]
+fn columns() {
+    new_ucmd()
+        .args(&["--columns", "name,diff_pct,speedup,throughput,variance,fst", "--threshold", "80",
+                "bench_output_2.txt", "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_columns.expected");
+}
+
+#[test]
+fn variance_diff() {
+    // bench_output_7.txt has the same means as bench_output_2.txt, but three
+    // times the variance.
+    new_ucmd()
+        .args(&["--variance-diff", "bench_output_2.txt", "bench_output_7.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_variance_diff.expected");
+}
+
+#[test]
+fn preserve_order() {
+    // bench_output_8.txt is bench_output_2.txt with its benchmarks shuffled.
+    new_ucmd()
+        .args(&["--preserve-order", "bench_output_8.txt", "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_order.expected");
+}
+
+#[test]
+fn demangle() {
+    // bench_output_9.txt is bench_output_3.txt with ::{{closure}} after
+    // every benchmark name.
+    new_ucmd()
+        .args(&["--demangle", "--label-snd", "bench_output_3.txt", "bench_output_2.txt", "bench_output_9.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input.expected");
+}
+
+#[test]
+fn sort_diff() {
+    new_ucmd()
+        .args(&["--sort", "diff", "bench_output_2.txt", "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_sort_diff.expected");
+}
+
+#[test]
+fn sort_throughput() {
+    // bench_output_10.txt is bench_output_3.txt without the throughput of
+    // the ac_ten and ac_two_bytes benchmarks.
+    new_ucmd()
+        .args(&["--sort", "throughput", "--throughput-only", "bench_output_2.txt", "bench_output_10.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_throughput.expected");
+}
+
+#[test]
+fn by_test_leaf() {
+    new_ucmd()
+        .args(&["--by-test-leaf", "bench_output_2.txt", "bench_output_4.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_leaf.expected");
+}
+
+#[test]
+fn labels() {
+    new_ucmd()
+        .args(&["--label-fst", "before", "--label-snd", "after", "bench_output_2.txt", "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_labels.expected");
+}
+
+#[test]
+fn min_diff_ns() {
+    new_ucmd()
+        .args(&["--min-diff-ns", "10", "bench_output_2.txt", "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_min_diff_ns.expected");
+}
+
+#[test]
+fn threshold_and_min_diff_ns() {
+    new_ucmd()
+        .args(&["--threshold", "1", "--min-diff-ns", "10", "bench_output_2.txt", "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_thresholds.expected");
+}
+
+#[test]
+fn auto_threshold() {
+    new_ucmd()
+        .args(&["--auto-threshold", "bench_output_2.txt", "bench_output_3.txt"])
+        .succeeds()
+        .stderr_is("NOTE: --auto-threshold is 2.10% (twice the median relative variance)")
+        .stdout_is_fixture("different_input_auto_threshold.expected");
+}
+
+#[test]
+fn threshold_regression() {
+    new_ucmd()
+        .args(&["--threshold-regression", "30", "bench_output_2.txt", "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_threshold_regression.expected");
+}
+
+#[test]
+fn threshold_regression_keeps_improvements() {
+    new_ucmd()
+        .args(&["--threshold-regression", "90", "bench_output_3.txt", "bench_output_2.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_swapped.expected");
+}
+
+#[test]
+fn threshold_improvement() {
+    new_ucmd()
+        .args(&["--threshold-improvement", "30", "bench_output_3.txt", "bench_output_2.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_threshold_improvement.expected");
+}
+
+#[test]
+fn threshold_improvement_keeps_regressions() {
+    new_ucmd()
+        .args(&["--threshold-improvement", "90", "bench_output_2.txt", "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input.expected");
+}
+
+#[test]
+fn normalize_names() {
+    new_ucmd()
+        .args(&["--normalize-names", "bench_output_2.txt", "bench_output_5.txt"])
+        .succeeds()
+        .stdout_is_fixture("different_input_normalized.expected");
+}
+
+#[test]
+fn match_pattern() {
+    // bench_output_4.txt has the benchmarks of bench_output_3.txt in a bench
+    // module.
+    new_ucmd()
+        .args(&["--quiet", "--match-pattern", r"(ac_\w+)$", "--threshold", "30",
+                "bench_output_2.txt", "bench_output_4.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_match_pattern.expected");
+}
+
+#[test]
+fn bounds() {
+    new_ucmd()
+        .args(&["--bounds", "bench_output_2.txt", "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_bounds.expected");
+}
+
+#[test]
+fn iterations() {
+    new_ucmd()
+        .args(&["--iterations", "bench_output_2.txt", "bench_output_6.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_iterations.expected");
+}
+
+#[test]
+fn baseline() {
+    new_ucmd()
+        .args(&["--baseline", "bench_output_2.txt", "bench_output_3.txt", "bench_output_2.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input.expected");
+}
+
+#[test]
+fn unknown_baseline() {
+    new_ucmd()
+        .args(&["--baseline", "nope", "bench_output_3.txt", "bench_output_2.txt"])
+        .fails()
+        .no_stdout()
+        .stderr_is("baseline nope is not <old>, <new> or best");
+}
+
+#[test]
+fn best_baseline() {
+    new_ucmd()
+        .args(&["--baseline", "best", "bench_output_13.txt", "bench_output_14.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_best_baseline.expected");
+}
+
+#[test]
+fn empty_input() {
+    new_ucmd()
+        .args(&["bench_output_empty.txt", "bench_output_2.txt"])
+        .fails()
+        .no_stdout()
+        .stderr_is("no benchmarks found in bench_output_empty.txt");
+}
+
+#[test]
+fn empty_prefix() {
+    new_ucmd()
+        .args(&["dense::", "nope::", "bench_output_1.txt"])
+        .fails()
+        .no_stdout()
+        .stderr_is("no benchmarks found in bench_output_1.txt with prefix nope::");
+}
+
+#[test]
+fn config() {
+    // config.json sets the threshold to 30 and the precision to 1, but the
+    // threshold given on the command line wins.
+    new_ucmd()
+        .args(&["--config", "config.json", "--threshold", "80", "bench_output_2.txt", "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_config.expected");
+}
+
+#[test]
+fn json() {
+    new_ucmd()
+        .args(&["--format", "json", "--threshold", "40", "bench_output_3.txt", "bench_output_2.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_json.expected");
+}
+
+#[test]
+fn csv() {
+    new_ucmd()
+        .args(&["--format", "csv", "--threshold", "80", "bench_output_2.txt", "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_csv.expected");
+}
+
+#[test]
+fn watch_stdin() {
+    new_ucmd()
+        .args(&["--watch", "old::", "new::", "-"])
+        .fails()
+        .no_stdout()
+        .stderr_is("--watch needs input files, it cannot watch stdin");
+}
+
+#[test]
+fn unknown_format() {
+    new_ucmd()
+        .args(&["--format", "jpeg", "bench_output_2.txt", "bench_output_3.txt"])
+        .fails()
+        .no_stdout()
+        .stderr_is("unknown format jpeg, expected one of: table, json, csv, unified, influx");
+}
+
+#[test]
+fn json_report() {
+    new_ucmd()
+        .args(&["--input-format", "json-array", "--threshold", "80", "report_2.json",
+                "report_3.json"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_json_report.expected");
+}
+
+#[test]
+fn percentile_metric() {
+    new_ucmd()
+        .args(&["--input-format", "json-array", "--metric", "p95", "report_4.json",
+                "report_5.json"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_metric.expected");
+}
+
+#[test]
+fn invalid_emit() {
+    new_ucmd()
+        .args(&["--emit", "csv:report.csv", "--emit", "markdown:report.md", "bench_output_2.txt",
+                "bench_output_3.txt"])
+        .fails()
+        .no_stdout()
+        .stderr_is("invalid --emit markdown:report.md, expected <format>:<path> with one of the \
+                    formats: table, json, csv, unified, influx");
+}
+
+#[test]
+fn emit_without_comparisons() {
+    let mut scene: second_law::Scene = new_scene!();
+    scene.subcmd_arg("benchcmp");
+    scene.ucmd()
+        .args(&["--quiet", "--emit", "csv:report,empty.csv", "--emit", "json:report.json",
+                "bench_output_1.txt", "bench_output_2.txt"])
+        .succeeds()
+        .no_stderr()
+        .no_stdout();
+    assert_eq!(scene.working_dir().read("report,empty.csv"),
+               "name,old_ns,new_ns,old_variance,new_variance,diff_ns,change_pct,direction\n");
+    assert_eq!(scene.working_dir().read("report.json"), "[]\n");
+}
+
+#[test]
+fn invalid_tag() {
+    new_ucmd()
+        .args(&["--format", "influx", "--tag", "commit", "bench_output_2.txt",
+                "bench_output_3.txt"])
+        .fails()
+        .no_stdout()
+        .stderr_is("invalid --tag commit, expected <key>=<value>");
+}
+
+#[test]
+fn unified() {
+    new_ucmd()
+        .args(&["--format", "unified", "--threshold", "80", "bench_output_2.txt",
+                "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_unified.expected");
+}
+
+#[test]
+fn fail_on_regression() {
+    new_ucmd()
+        .args(&["--github-annotations", "--fail-on-regression", "50", "--threshold", "30",
+                "bench_output_2.txt", "bench_output_3.txt"])
+        .fails()
+        .stderr_is("2 benchmarks regressed by 50% or more")
+        .stdout_is_fixture("different_input_annotations.expected");
+}
+
+#[test]
+fn fail_on_variance_increase() {
+    // bench_output_7.txt has three times the variance of bench_output_2.txt.
+    new_ucmd()
+        .args(&["--variance-diff", "--fail-on-variance-increase", "150", "bench_output_2.txt",
+                "bench_output_7.txt"])
+        .fails()
+        .stderr_is("the variance of 14 benchmarks grew by 150% or more")
+        .stdout_is_fixture("different_input_variance_diff.expected");
+}
+
+#[test]
+fn require() {
+    new_ucmd()
+        .args(&["--require", "ac_ten_bytes,dense::ac_ten_bytes", "--threshold", "90",
+                "bench_output_2.txt", "bench_output_3.txt"])
+        .fails()
+        .stderr_is("required benchmarks not in both old and new: dense::ac_ten_bytes");
+}
+
+#[test]
+fn github_annotations() {
+    new_ucmd()
+        .args(&["--github-annotations", "--threshold", "40", "bench_output_3.txt", "bench_output_2.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_notices.expected");
+}
+
+#[test]
+fn ci_summary() {
+    new_ucmd()
+        .args(&["--ci-summary", "--threshold", "30", "bench_output_2.txt", "bench_output_3.txt"])
+        .succeeds()
+        .stderr_is("benchcmp: 5 regressions, 0 improvements, 0 unchanged (geomean +51.81%)");
+}
+
+#[test]
+fn summary_weighted() {
+    new_ucmd()
+        .args(&["--ci-summary", "--summary-weighted", "--threshold", "30", "bench_output_2.txt",
+                "bench_output_3.txt"])
+        .succeeds()
+        .stderr_is("benchcmp: 5 regressions, 0 improvements, 0 unchanged (weighted geomean +69.83%)");
+}
+
+#[test]
+fn symmetric_pct() {
+    // the same percentages as bench_output_2.txt to bench_output_3.txt, negated
+    new_ucmd()
+        .args(&["--symmetric-pct", "--threshold", "30", "bench_output_3.txt",
+                "bench_output_2.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_symmetric.expected");
+}
+
+#[test]
+fn ratio_base_new() {
+    // relative to new, which is the smaller one here, like --symmetric-pct
+    new_ucmd()
+        .args(&["--ratio-base", "new", "--threshold", "30", "bench_output_3.txt",
+                "bench_output_2.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_symmetric.expected");
+}
+
+#[test]
+fn rank() {
+    new_ucmd()
+        .args(&["--rank", "bench_output_2.txt", "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_rank.expected");
+}
+
+#[test]
+fn spark() {
+    new_ucmd()
+        .args(&["--spark", "--ascii", "--threshold", "20", "bench_output_2.txt",
+                "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_spark.expected");
+}
+
+#[test]
+fn combined_diff() {
+    new_ucmd()
+        .args(&["--combined-diff", "--threshold", "20", "bench_output_2.txt",
+                "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_combined_diff.expected");
+}
+
+#[test]
+fn round() {
+    new_ucmd()
+        .args(&["--round", "2", "--threshold", "20", "bench_output_2.txt",
+                "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_round.expected");
+}
+
+#[test]
+fn arrows() {
+    new_ucmd()
+        .args(&["--arrows", "--threshold", "20", "bench_output_3.txt", "bench_output_2.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_arrows.expected");
+}
+
+#[test]
+fn relative() {
+    new_ucmd()
+        .args(&["--relative", "--threshold", "30", "bench_output_2.txt", "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_relative.expected");
+}
+
+#[test]
+fn plain() {
+    new_ucmd()
+        .args(&["--plain", "--threshold", "30", "bench_output_2.txt", "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_plain.expected");
+}
+
+#[test]
+fn by_throughput() {
+    // bench_output_11.txt only reports the throughput of bench_output_3.txt
+    new_ucmd()
+        .args(&["--by-throughput", "--threshold", "30", "bench_output_2.txt",
+                "bench_output_11.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_by_throughput.expected");
+}
+
+#[test]
+fn auto_metric() {
+    // Only the ac_ benchmarks report a throughput.
+    new_ucmd()
+        .args(&["--auto-metric", "bench_output_13.txt", "bench_output_14.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_auto_metric.expected");
+}
+
+#[test]
+fn min_samples() {
+    new_ucmd()
+        .args(&["--min-samples", "5000", "--threshold", "90", "bench_output_2.txt",
+                "bench_output_6.txt"])
+        .succeeds()
+        .stderr_is("WARNING: benchmarks with fewer than 5000 iterations: \
+                    ac_one_prefix_byte_every_match, ac_one_prefix_byte_random, \
+                    ac_ten_diff_prefix, ac_ten_one_prefix_byte_no_match, ac_two_bytes, \
+                    ac_two_one_prefix_byte_every_match, ac_two_one_prefix_byte_random");
+}
+
+#[test]
+fn coverage() {
+    new_ucmd()
+        .args(&["--coverage", "--quiet", "--threshold", "90", "bench_output_1.txt",
+                "bench_output_2.txt"])
+        .succeeds()
+        .no_stdout()
+        .stderr_is("benchcmp: bench_output_1.txt has 84 benchmarks, bench_output_2.txt has 14, 0 \
+                    in common");
+}
+
+#[test]
+fn totals() {
+    new_ucmd()
+        .args(&["--totals", "--threshold", "30", "bench_output_2.txt", "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_totals.expected");
+}
+
+#[test]
+fn threshold_sigmas() {
+    // ac_two_diff_prefix changes by 14ns but has a variance of 32ns and
+    // 57ns, so it is filtered out.
+    new_ucmd()
+        .args(&["--threshold-sigmas", "1", "--variance-is-stddev", "bench_output_2.txt", "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_sigmas.expected");
+}
+
+#[test]
+fn threshold_sigmas_of_range() {
+    // The variance is a range of four standard deviations by default.
+    new_ucmd()
+        .args(&["--threshold-sigmas", "4", "bench_output_2.txt", "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_sigmas.expected");
+}
+
+#[test]
+fn avg() {
+    new_ucmd()
+        .args(&["--avg", "bench_output_2.txt,bench_output_3.txt", "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_avg.expected");
+}
+
+#[test]
+fn exclude() {
+    new_ucmd()
+        .args(&["--exclude", "prefix|two", "bench_output_2.txt", "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_excluded.expected");
+}
+
+#[test]
+fn rename_map() {
+    new_ucmd()
+        .args(&["--quiet", "--rename-map", "rename_map.txt", "bench_output_1.txt",
+                "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_renamed.expected");
+}
+
+#[test]
+fn reduce_min() {
+    // the fastest of each pair of runs is the one from bench_output_2.txt
+    new_ucmd()
+        .args(&["--avg", "--reduce", "min", "bench_output_2.txt,bench_output_3.txt",
+                "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input_reduce_min.expected");
+}
+
+#[test]
+fn save_and_compare() {
+    let mut scene: second_law::Scene = new_scene!();
+    scene.subcmd_arg("benchcmp");
+    scene.ucmd()
+        .args(&["save", "base", "bench_output_2.txt"])
+        .succeeds()
+        .no_stderr()
+        .no_stdout();
+    scene.ucmd()
+        .args(&["compare", "--label-fst", "bench_output_2.txt", "base", "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input.expected");
+}
+
+#[test]
+fn merge_and_compare() {
+    // shards holds the benchmarks of bench_output_2.txt split over two files.
+    let mut scene: second_law::Scene = new_scene!();
+    scene.subcmd_arg("benchcmp");
+    scene.ucmd()
+        .args(&["merge", "merged.txt", "shards"])
+        .succeeds()
+        .no_stderr()
+        .no_stdout();
+    scene.ucmd()
+        .args(&["--label-fst", "bench_output_2.txt", "merged.txt", "bench_output_3.txt"])
+        .succeeds()
+        .no_stderr()
+        .stdout_is_fixture("different_input.expected");
+}
+
+/// Runs benchcmp with the given arguments in tests/fixtures and returns its
+/// exit status, which second_law doesn't tell apart beyond failure.
+fn exit_code(args: &[&str]) -> Option<i32> {
+    use std::env;
+    use std::process::Command;
+
+    // The binary is next to the deps directory of this test.
+    let mut bin = env::current_exe().unwrap();
+    bin.pop();
+    bin.pop();
+    bin.push(if cfg!(target_os = "windows") {
+        format!("{}.exe", env!("CARGO_PKG_NAME"))
+    } else {
+        env!("CARGO_PKG_NAME").to_string()
+    });
+    Command::new(bin)
+        .arg("benchcmp")
+        .args(args)
+        .current_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures"))
+        .output()
+        .unwrap()
+        .status
+        .code()
+}
+
+#[test]
+fn unreadable_input_exit_code() {
+    assert_eq!(exit_code(&["does_not_exist_old.txt", "does_not_exist_new.txt"]), Some(3));
+}
+
+#[test]
+fn unwritable_output_exit_code() {
+    assert_eq!(exit_code(&["--emit", "csv:no_such_dir/report.csv", "bench_output_2.txt",
+                           "bench_output_3.txt"]),
+               Some(4));
+}